@@ -0,0 +1,254 @@
+use twitch_message::messages::{Message, Privmsg, TwitchMessage};
+
+/// A subscription, gift, raid, cheer, announcement or ritual that happened in a channel
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct ChannelEvent {
+    pub channel: String,
+    /// The login of the user that caused this event
+    pub user: String,
+    pub display_name: Option<String>,
+    pub user_id: Option<String>,
+    /// The message the user attached, if any
+    pub message: Option<String>,
+    pub kind: ChannelEventKind,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ChannelEventKind {
+    Sub {
+        plan: SubPlan,
+    },
+    Resub {
+        plan: SubPlan,
+        months: u32,
+        /// Only available if the user chose to share it
+        streak: Option<u32>,
+    },
+    SubGift {
+        /// `user` is `ananonymousgifter` for anonymous gifts
+        anonymous: bool,
+        recipient: String,
+        plan: SubPlan,
+        months: u32,
+    },
+    MysteryGift {
+        anonymous: bool,
+        count: u32,
+        plan: SubPlan,
+    },
+    Raid {
+        viewers: u32,
+    },
+    Cheer {
+        bits: u32,
+    },
+    Announcement {
+        color: Option<String>,
+    },
+    Ritual {
+        name: String,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum SubPlan {
+    Prime,
+    Tier1,
+    Tier2,
+    Tier3,
+    Other(String),
+}
+
+impl SubPlan {
+    fn parse(input: &str) -> Self {
+        match input {
+            "Prime" => Self::Prime,
+            "1000" => Self::Tier1,
+            "2000" => Self::Tier2,
+            "3000" => Self::Tier3,
+            plan => Self::Other(plan.to_string()),
+        }
+    }
+}
+
+impl ChannelEvent {
+    /// Creates an event from a USERNOTICE, or a PRIVMSG that has bits attached
+    pub fn from_message(msg: &Message<'_>) -> Option<Self> {
+        let tag = |key: &str| msg.tags.get(key).map(|s| s.to_string());
+        let num = |key: &str| tag(key).and_then(|s| s.parse::<u32>().ok());
+        let plan = || SubPlan::parse(tag("msg-param-sub-plan").as_deref().unwrap_or_default());
+        // twitch mostly sends anonymous gifts as regular gifts from this user
+        let anonymous = |id: &str| {
+            id.starts_with("anon") || tag("login").as_deref() == Some("ananonymousgifter")
+        };
+
+        let kind = match msg.as_enum() {
+            TwitchMessage::Privmsg(..) => ChannelEventKind::Cheer { bits: num("bits")? },
+            TwitchMessage::UserNotice(..) => match tag("msg-id")?.as_str() {
+                "sub" => ChannelEventKind::Sub { plan: plan() },
+                "resub" => ChannelEventKind::Resub {
+                    plan: plan(),
+                    months: num("msg-param-cumulative-months").unwrap_or(1),
+                    streak: num("msg-param-streak-months")
+                        .filter(|_| tag("msg-param-should-share-streak").as_deref() == Some("1")),
+                },
+                id @ ("subgift" | "anonsubgift") => ChannelEventKind::SubGift {
+                    anonymous: anonymous(id),
+                    recipient: tag("msg-param-recipient-user-name")?,
+                    plan: plan(),
+                    months: num("msg-param-gift-months")
+                        .or_else(|| num("msg-param-months"))
+                        .unwrap_or(1),
+                },
+                id @ ("submysterygift" | "anonsubmysterygift") => ChannelEventKind::MysteryGift {
+                    anonymous: anonymous(id),
+                    count: num("msg-param-mass-gift-count").unwrap_or(1),
+                    plan: plan(),
+                },
+                "raid" => ChannelEventKind::Raid {
+                    viewers: num("msg-param-viewerCount").unwrap_or_default(),
+                },
+                "announcement" => ChannelEventKind::Announcement {
+                    color: tag("msg-param-color"),
+                },
+                "ritual" => ChannelEventKind::Ritual {
+                    name: tag("msg-param-ritual-name")?,
+                },
+                _ => return None,
+            },
+            _ => return None,
+        };
+
+        let user = match &kind {
+            ChannelEventKind::Cheer { .. } => msg
+                .as_typed_message::<Privmsg>()
+                .map(|pm| pm.sender.to_string()),
+            ChannelEventKind::Raid { .. } => tag("msg-param-login").or_else(|| tag("login")),
+            _ => tag("login"),
+        };
+
+        Some(Self {
+            channel: msg.args.first()?.to_string(),
+            user: user?,
+            display_name: tag("display-name").filter(|s| !s.is_empty()),
+            user_id: tag("user-id"),
+            message: msg.data.as_ref().map(|s| s.to_string()),
+            kind,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event(line: &str) -> Option<ChannelEvent> {
+        let msg = twitch_message::parse(line).unwrap().message;
+        ChannelEvent::from_message(&msg)
+    }
+
+    #[test]
+    fn channel_event_from_message() {
+        use ChannelEventKind::*;
+
+        let tests = [
+            (
+                "@badge-info=subscriber/0;badges=subscriber/0,premium/1;color=#0000FF;display-name=Museun;emotes=;flags=;id=db25007f-7a18-43eb-9379-80131e44d633;login=museun;mod=0;msg-id=sub;msg-param-cumulative-months=1;msg-param-months=0;msg-param-should-share-streak=0;msg-param-sub-plan-name=Channel\\sSubscription;msg-param-sub-plan=Prime;room-id=23196011;subscriber=1;system-msg=Museun\\ssubscribed\\swith\\sPrime.;tmi-sent-ts=1682000000000;user-id=23196011;user-type= :tmi.twitch.tv USERNOTICE #shaken_bot",
+                ("museun", None, Sub { plan: SubPlan::Prime }),
+            ),
+            (
+                "@badge-info=subscriber/10;badges=subscriber/9;color=;display-name=Museun;emotes=;flags=;id=5f7bb3e1-5b56-4a9e-8d5d-5b1f1c0a1e2d;login=museun;mod=0;msg-id=resub;msg-param-cumulative-months=10;msg-param-months=0;msg-param-should-share-streak=1;msg-param-streak-months=5;msg-param-sub-plan-name=Channel\\sSubscription;msg-param-sub-plan=1000;room-id=23196011;subscriber=1;system-msg=Museun\\ssubscribed\\sat\\sTier\\s1.;tmi-sent-ts=1682000000000;user-id=23196011;user-type= :tmi.twitch.tv USERNOTICE #shaken_bot :great stream",
+                (
+                    "museun",
+                    Some("great stream"),
+                    Resub { plan: SubPlan::Tier1, months: 10, streak: Some(5) },
+                ),
+            ),
+            (
+                "@badge-info=subscriber/3;badges=subscriber/3;color=;display-name=Museun;emotes=;flags=;id=0a5c3a1b-1f8e-4e0c-9f4b-6a1b2c3d4e5f;login=museun;mod=0;msg-id=resub;msg-param-cumulative-months=3;msg-param-months=0;msg-param-should-share-streak=0;msg-param-streak-months=3;msg-param-sub-plan-name=Channel\\sSubscription;msg-param-sub-plan=3000;room-id=23196011;subscriber=1;system-msg=Museun\\ssubscribed\\sat\\sTier\\s3.;tmi-sent-ts=1682000000000;user-id=23196011;user-type= :tmi.twitch.tv USERNOTICE #shaken_bot",
+                ("museun", None, Resub { plan: SubPlan::Tier3, months: 3, streak: None }),
+            ),
+            (
+                "@badge-info=;badges=;color=;display-name=AnAnonymousGifter;emotes=;flags=;id=b1818e3c-0005-490f-ad0a-804957ddd760;login=ananonymousgifter;mod=0;msg-id=anonsubgift;msg-param-fun-string=FunStringTwo;msg-param-gift-months=1;msg-param-months=4;msg-param-origin-id=da\\s39\\sa3\\see\\s5e;msg-param-recipient-display-name=Museun;msg-param-recipient-id=23196011;msg-param-recipient-user-name=museun;msg-param-sub-plan-name=Channel\\sSubscription;msg-param-sub-plan=1000;room-id=23196011;subscriber=0;system-msg=An\\sanonymous\\suser\\sgifted\\sa\\sTier\\s1\\ssub\\sto\\sMuseun!;tmi-sent-ts=1682000000000;user-id=274598607;user-type= :tmi.twitch.tv USERNOTICE #shaken_bot",
+                (
+                    "ananonymousgifter",
+                    None,
+                    SubGift {
+                        anonymous: true,
+                        recipient: String::from("museun"),
+                        plan: SubPlan::Tier1,
+                        months: 1,
+                    },
+                ),
+            ),
+            (
+                "@badge-info=;badges=;color=;display-name=Museun;emotes=;flags=;id=7a2d7f3a-4b6c-4e2b-9d1e-2f3a4b5c6d7e;login=museun;mod=0;msg-id=submysterygift;msg-param-mass-gift-count=5;msg-param-origin-id=d0\\s2f\\s1e;msg-param-sender-count=20;msg-param-sub-plan=2000;room-id=23196011;subscriber=0;system-msg=Museun\\sis\\sgifting\\s5\\sTier\\s2\\sSubs!;tmi-sent-ts=1682000000000;user-id=23196011;user-type= :tmi.twitch.tv USERNOTICE #shaken_bot",
+                (
+                    "museun",
+                    None,
+                    MysteryGift { anonymous: false, count: 5, plan: SubPlan::Tier2 },
+                ),
+            ),
+            (
+                "@badge-info=;badges=;color=#FF0000;display-name=Museun;emotes=;flags=;id=3d830f12-795c-447d-af3c-ea05e40fbddb;login=museun;mod=0;msg-id=raid;msg-param-displayName=Museun;msg-param-login=museun;msg-param-profileImageURL=https://example.com/museun.png;msg-param-viewerCount=42;room-id=23196011;subscriber=0;system-msg=42\\sraiders\\sfrom\\sMuseun\\shave\\sjoined!;tmi-sent-ts=1682000000000;user-id=23196011;user-type= :tmi.twitch.tv USERNOTICE #shaken_bot",
+                ("museun", None, Raid { viewers: 42 }),
+            ),
+            (
+                "@badge-info=;badges=broadcaster/1;color=;display-name=Museun;emotes=;flags=;id=e9a1b2c3-d4e5-4f60-8a9b-0c1d2e3f4a5b;login=museun;mod=0;msg-id=announcement;msg-param-color=PRIMARY;room-id=23196011;subscriber=0;system-msg=;tmi-sent-ts=1682000000000;user-id=23196011;user-type= :tmi.twitch.tv USERNOTICE #shaken_bot :hello everyone",
+                (
+                    "museun",
+                    Some("hello everyone"),
+                    Announcement { color: Some(String::from("PRIMARY")) },
+                ),
+            ),
+            (
+                "@badge-info=;badges=;color=;display-name=Museun;emotes=30259:0-6;id=37feed0f-b9c7-4c3a-b475-21c6c6d21c3d;login=museun;mod=0;msg-id=ritual;msg-param-ritual-name=new_chatter;room-id=23196011;subscriber=0;system-msg=@Museun\\sis\\snew\\shere!;tmi-sent-ts=1682000000000;user-id=23196011;user-type= :tmi.twitch.tv USERNOTICE #shaken_bot :HeyGuys",
+                (
+                    "museun",
+                    Some("HeyGuys"),
+                    Ritual { name: String::from("new_chatter") },
+                ),
+            ),
+            (
+                "@badge-info=;badges=bits/100;bits=100;color=#FF0000;display-name=Museun;emotes=;id=b34ccfc7-4977-403a-8a94-33c6bac34fb8;mod=0;room-id=23196011;subscriber=0;tmi-sent-ts=1682000000000;turbo=0;user-id=23196011;user-type= :museun!museun@museun.tmi.twitch.tv PRIVMSG #shaken_bot :cheer100 nice",
+                ("museun", Some("cheer100 nice"), Cheer { bits: 100 }),
+            ),
+        ];
+
+        for (line, (user, message, kind)) in tests {
+            let event = event(line).unwrap_or_else(|| panic!("expected an event for: {line}"));
+            assert_eq!(event.channel, "#shaken_bot");
+            assert_eq!(event.user, user);
+            assert_eq!(event.message.as_deref(), message);
+            assert_eq!(event.kind, kind);
+        }
+    }
+
+    #[test]
+    fn channel_event_from_message_none() {
+        for line in [
+            // a regular message
+            "@badge-info=;badges=;color=;display-name=Museun;emotes=;id=b34ccfc7-4977-403a-8a94-33c6bac34fb8;mod=0;room-id=23196011;subscriber=0;tmi-sent-ts=1682000000000;turbo=0;user-id=23196011;user-type= :museun!museun@museun.tmi.twitch.tv PRIVMSG #shaken_bot :hello",
+            // bits that aren't a number
+            "@bits=lots;display-name=Museun;user-id=23196011 :museun!museun@museun.tmi.twitch.tv PRIVMSG #shaken_bot :cheer",
+            // an unknown msg-id
+            "@badge-info=;badges=;display-name=Museun;login=museun;msg-id=unraid;room-id=23196011;system-msg=The\\sraid\\shas\\sbeen\\scanceled.;tmi-sent-ts=1682000000000;user-id=23196011 :tmi.twitch.tv USERNOTICE #shaken_bot",
+            // no msg-id
+            "@login=museun;user-id=23196011 :tmi.twitch.tv USERNOTICE #shaken_bot",
+            // a gift without a recipient
+            "@login=museun;msg-id=subgift;msg-param-sub-plan=1000;user-id=23196011 :tmi.twitch.tv USERNOTICE #shaken_bot",
+            // a ritual without a name
+            "@login=museun;msg-id=ritual;user-id=23196011 :tmi.twitch.tv USERNOTICE #shaken_bot :HeyGuys",
+            // a sub without a login
+            "@msg-id=sub;msg-param-sub-plan=1000 :tmi.twitch.tv USERNOTICE #shaken_bot",
+            // not a chat message
+            ":museun!museun@museun.tmi.twitch.tv JOIN #shaken_bot",
+            "PING :tmi.twitch.tv",
+        ] {
+            assert_eq!(event(line), None, "{line}");
+        }
+    }
+}
@@ -0,0 +1,107 @@
+use std::{
+    collections::BTreeSet,
+    path::{Path, PathBuf},
+};
+
+/// Persists the channels we've joined so they can be rejoined after a restart
+///
+/// These are called from tokio's blocking thread pool, so they can do blocking I/O
+pub trait ChannelStore: Send + Sync + 'static {
+    fn load(&self) -> std::io::Result<Vec<String>>;
+    fn join(&self, channel: &str) -> std::io::Result<()>;
+    fn part(&self, channel: &str) -> std::io::Result<()>;
+}
+
+/// A [`ChannelStore`] that keeps one channel per line in a file
+pub struct FileChannelStore {
+    path: PathBuf,
+    lock: parking_lot::Mutex<()>,
+}
+
+impl FileChannelStore {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            lock: parking_lot::Mutex::new(()),
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    fn read(&self) -> std::io::Result<BTreeSet<String>> {
+        match std::fs::read_to_string(&self.path) {
+            Ok(data) => Ok(data
+                .lines()
+                .map(str::trim)
+                .filter(|s| !s.is_empty())
+                .map(ToString::to_string)
+                .collect()),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(BTreeSet::new()),
+            Err(err) => Err(err),
+        }
+    }
+
+    fn update(&self, update: impl FnOnce(&mut BTreeSet<String>) -> bool) -> std::io::Result<()> {
+        let _guard = self.lock.lock();
+        let mut channels = self.read()?;
+        if !update(&mut channels) {
+            return Ok(());
+        }
+
+        let data = channels.into_iter().fold(String::new(), |mut a, c| {
+            a.push_str(&c);
+            a.push('\n');
+            a
+        });
+        std::fs::write(&self.path, data)
+    }
+}
+
+impl ChannelStore for FileChannelStore {
+    fn load(&self) -> std::io::Result<Vec<String>> {
+        let _guard = self.lock.lock();
+        self.read().map(|set| set.into_iter().collect())
+    }
+
+    fn join(&self, channel: &str) -> std::io::Result<()> {
+        self.update(|set| set.insert(channel.to_string()))
+    }
+
+    fn part(&self, channel: &str) -> std::io::Result<()> {
+        self.update(|set| set.remove(channel))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn file_channel_store() {
+        let path = std::env::temp_dir().join(format!(
+            "twitch_message_bot_channel_store_{}",
+            std::process::id()
+        ));
+        let _ = std::fs::remove_file(&path);
+
+        let store = FileChannelStore::new(&path);
+        assert!(store.load().unwrap().is_empty());
+
+        store.join("#museun").unwrap();
+        store.join("#shaken_bot").unwrap();
+        store.join("#museun").unwrap();
+        assert_eq!(store.load().unwrap(), ["#museun", "#shaken_bot"]);
+
+        store.part("#museun").unwrap();
+        store.part("#not_joined").unwrap();
+        assert_eq!(store.load().unwrap(), ["#shaken_bot"]);
+
+        // a new store sees what the old one wrote
+        let store = FileChannelStore::new(&path);
+        assert_eq!(store.load().unwrap(), ["#shaken_bot"]);
+
+        let _ = std::fs::remove_file(&path);
+    }
+}
@@ -0,0 +1,447 @@
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
+
+use tokio::{fs::File, io::AsyncWriteExt as _};
+use twitch_message::{messages::Privmsg, Badge};
+
+use crate::{
+    util::{channel_login, DateTime},
+    Plugin, Writer,
+};
+
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum LogFormat {
+    /// `[12:34:56] <museun> hello world`
+    #[default]
+    Text,
+    /// One JSON object per line, including all of the tags
+    JsonLines,
+}
+
+impl LogFormat {
+    const fn extension(&self) -> &'static str {
+        match self {
+            Self::Text => "log",
+            Self::JsonLines => "jsonl",
+        }
+    }
+}
+
+/// A [`Plugin`] that writes chat to a file per channel, per day
+///
+/// Files are written to `{dir}/{channel}/{yyyy-mm-dd}.{ext}`. When a file grows
+/// past the max size, the next one is `{yyyy-mm-dd}.1.{ext}`, and so on
+pub struct ChatLogger {
+    dir: PathBuf,
+    format: LogFormat,
+    max_size: Option<u64>,
+    compress: bool,
+    own_messages: bool,
+    files: HashMap<String, LogFile>,
+}
+
+impl ChatLogger {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self {
+            dir: dir.into(),
+            format: LogFormat::default(),
+            max_size: None,
+            compress: false,
+            own_messages: true,
+            files: HashMap::new(),
+        }
+    }
+
+    pub fn with_format(self, format: LogFormat) -> Self {
+        Self { format, ..self }
+    }
+
+    /// Start a new file once the current one is larger than `max_size` bytes
+    pub fn with_max_size(self, max_size: u64) -> Self {
+        Self {
+            max_size: Some(max_size),
+            ..self
+        }
+    }
+
+    /// Gzip files once they've been rotated or the channel has been parted
+    ///
+    /// Files that were still open when the bot stopped are compressed once the channel
+    /// logs on a later day
+    pub fn with_compression(self, compress: bool) -> Self {
+        Self { compress, ..self }
+    }
+
+    /// Also log the messages we send, this requires
+    /// [`Config::with_echo_own_messages`](crate::Config::with_echo_own_messages)
+    pub fn with_own_messages(self, own_messages: bool) -> Self {
+        Self {
+            own_messages,
+            ..self
+        }
+    }
+
+    async fn log(&mut self, msg: &Privmsg<'_>) {
+        let ts = msg
+            .tags
+            .get("tmi-sent-ts")
+            .and_then(|ts| ts.parse::<i64>().ok())
+            .unwrap_or_else(|| {
+                std::time::SystemTime::now()
+                    .duration_since(std::time::UNIX_EPOCH)
+                    .map(|d| d.as_millis() as i64)
+                    .unwrap_or_default()
+            });
+
+        let now = DateTime::from_unix(ts / 1000);
+        let mut line = match self.format {
+            LogFormat::Text => Self::format_text(msg, &now),
+            LogFormat::JsonLines => Self::format_json(msg, ts),
+        };
+        line.push('\n');
+
+        let channel = channel_login(&msg.channel);
+        if let Err(err) = self.write(&channel, &now, line.as_bytes()).await {
+            log::warn!("cannot write chat log for {channel}: {err}");
+        }
+    }
+
+    async fn write(&mut self, channel: &str, now: &DateTime, data: &[u8]) -> std::io::Result<()> {
+        let date = now.date();
+        let len = data.len() as u64;
+
+        let rotate = match self.files.get(channel) {
+            Some(file) if file.date != date => true,
+            Some(file) => is_full(file.size, len, self.max_size),
+            None => false,
+        };
+
+        if rotate {
+            if let Some(file) = self.files.remove(channel) {
+                file.finish(self.compress).await?;
+            }
+        }
+
+        let file = match self.files.entry(channel.to_string()) {
+            std::collections::hash_map::Entry::Occupied(file) => file.into_mut(),
+            std::collections::hash_map::Entry::Vacant(entry) => {
+                let dir = self.dir.join(channel);
+                if self.compress {
+                    let (dir, date, ext) = (dir.clone(), date.clone(), self.format.extension());
+                    let task = move || compress_stale(&dir, &date, ext);
+                    if let Ok(Err(err)) = tokio::task::spawn_blocking(task).await {
+                        log::warn!("cannot compress old chat logs for {channel}: {err}");
+                    }
+                }
+                let file = LogFile::open(&dir, &date, self.format, self.max_size, len).await?;
+                entry.insert(file)
+            }
+        };
+
+        file.file.write_all(data).await?;
+        file.file.flush().await?;
+        file.size += len;
+        Ok(())
+    }
+
+    fn format_text(msg: &Privmsg<'_>, now: &DateTime) -> String {
+        let name = msg
+            .tags
+            .get("display-name")
+            .filter(|s| !s.is_empty())
+            .map(|s| s.to_string())
+            .unwrap_or_else(|| msg.sender.to_string());
+
+        match action(&msg.data) {
+            Some(action) => format!("[{}] * {name} {action}", now.time()),
+            None => format!("[{}] <{name}> {}", now.time(), msg.data),
+        }
+    }
+
+    fn format_json(msg: &Privmsg<'_>, ts: i64) -> String {
+        let tags = msg
+            .tags
+            .iter()
+            .map(|(k, v)| (k.to_string(), serde_json::Value::from(v.to_string())))
+            .collect::<serde_json::Map<_, _>>();
+
+        let badges = msg
+            .badges()
+            .map(|Badge { name, version }| serde_json::json!({ "name": name, "version": version }))
+            .collect::<Vec<_>>();
+
+        // id:start-end,start-end/id:start-end
+        let emotes = msg
+            .tags
+            .get("emotes")
+            .into_iter()
+            .flat_map(|s| s.split('/'))
+            .filter_map(|s| s.split_once(':'))
+            .map(|(id, ranges)| {
+                let ranges = ranges.split(',').collect::<Vec<_>>();
+                serde_json::json!({ "id": id, "ranges": ranges })
+            })
+            .collect::<Vec<_>>();
+
+        let (data, is_action) = match action(&msg.data) {
+            Some(action) => (action, true),
+            None => (&*msg.data, false),
+        };
+
+        serde_json::json!({
+            "ts": ts,
+            "channel": &*msg.channel,
+            "sender": &*msg.sender,
+            "data": data,
+            "action": is_action,
+            "badges": badges,
+            "emotes": emotes,
+            "tags": tags,
+        })
+        .to_string()
+    }
+}
+
+#[async_trait::async_trait]
+impl Plugin for ChatLogger {
+    async fn on_privmsg<'a, 'b>(&'a mut self, message: &'b Privmsg<'static>, _writer: Writer) {
+        self.log(message).await
+    }
+
+    async fn on_own_privmsg<'a, 'b>(&'a mut self, message: &'b Privmsg<'static>, _writer: Writer) {
+        if self.own_messages {
+            self.log(message).await
+        }
+    }
+
+    async fn on_part<'a, 'b>(&'a mut self, channel: &'b str) {
+        let channel = channel_login(channel);
+        if let Some(file) = self.files.remove(&channel) {
+            if let Err(err) = file.finish(self.compress).await {
+                log::warn!("cannot close chat log for {channel}: {err}");
+            }
+        }
+    }
+}
+
+struct LogFile {
+    path: PathBuf,
+    date: String,
+    size: u64,
+    file: File,
+}
+
+impl LogFile {
+    /// Opens the first file for `date` that has room for a write of `len` bytes
+    async fn open(
+        dir: &Path,
+        date: &str,
+        format: LogFormat,
+        max_size: Option<u64>,
+        len: u64,
+    ) -> std::io::Result<Self> {
+        tokio::fs::create_dir_all(dir).await?;
+
+        let ext = format.extension();
+        for index in 0.. {
+            let path = match index {
+                0 => dir.join(format!("{date}.{ext}")),
+                n => dir.join(format!("{date}.{n}.{ext}")),
+            };
+
+            // a compressed file has already been rotated
+            if tokio::fs::metadata(gz_path(&path)).await.is_ok() {
+                continue;
+            }
+
+            let size = match tokio::fs::metadata(&path).await {
+                Ok(md) => md.len(),
+                Err(err) if err.kind() == std::io::ErrorKind::NotFound => 0,
+                Err(err) => return Err(err),
+            };
+
+            if is_full(size, len, max_size) {
+                continue;
+            }
+
+            let file = tokio::fs::OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(&path)
+                .await?;
+
+            return Ok(Self {
+                path,
+                date: date.to_string(),
+                size,
+                file,
+            });
+        }
+
+        unreachable!("there is always a next index")
+    }
+
+    async fn finish(mut self, compress: bool) -> std::io::Result<()> {
+        self.file.flush().await?;
+        drop(self.file);
+
+        if !compress {
+            return Ok(());
+        }
+
+        let path = self.path;
+        tokio::task::spawn_blocking(move || compress_file(&path))
+            .await
+            .map_err(|err| std::io::Error::new(std::io::ErrorKind::Other, err))?
+    }
+}
+
+/// A file is full once a write would take it past `max_size`. An empty file takes any write
+fn is_full(size: u64, len: u64, max_size: Option<u64>) -> bool {
+    matches!(max_size, Some(max) if size > 0 && size + len > max)
+}
+
+/// The text of a `/me` message
+fn action(data: &str) -> Option<&str> {
+    let action = data.strip_prefix("\x01ACTION ")?;
+    Some(action.strip_suffix('\x01').unwrap_or(action))
+}
+
+fn gz_path(path: &Path) -> PathBuf {
+    let mut path = path.as_os_str().to_owned();
+    path.push(".gz");
+    path.into()
+}
+
+/// Compresses the logs in `dir` from before `date`
+fn compress_stale(dir: &Path, date: &str, ext: &str) -> std::io::Result<()> {
+    let entries = match std::fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+        Err(err) => return Err(err),
+    };
+
+    let ext = format!(".{ext}");
+    for entry in entries {
+        let path = entry?.path();
+        let Some(name) = path.file_name().and_then(|s| s.to_str()) else {
+            continue;
+        };
+
+        if name.ends_with(&ext) && !name.starts_with(date) {
+            compress_file(&path)?;
+        }
+    }
+
+    Ok(())
+}
+
+fn compress_file(path: &Path) -> std::io::Result<()> {
+    let mut input = std::fs::File::open(path)?;
+    let output = std::fs::File::create(gz_path(path))?;
+
+    let mut encoder = flate2::write::GzEncoder::new(output, flate2::Compression::default());
+    std::io::copy(&mut input, &mut encoder)?;
+    encoder.finish()?;
+
+    std::fs::remove_file(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use twitch_message::IntoStatic as _;
+
+    use super::*;
+
+    fn privmsg(data: &str) -> Privmsg<'static> {
+        // 2023-04-20 14:13:20
+        let line = format!(
+            "@display-name=Museun;id=1;tmi-sent-ts=1682000000000;user-id=23196011 \
+             :museun!museun@museun.tmi.twitch.tv PRIVMSG #Shaken_Bot :{data}"
+        );
+        let msg = twitch_message::parse(&line).unwrap().message.into_static();
+        msg.as_typed_message::<Privmsg>().unwrap().clone()
+    }
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!(
+            "twitch_message_bot_chat_log_{name}_{}",
+            std::process::id()
+        ));
+        let _ = std::fs::remove_dir_all(&dir);
+        dir
+    }
+
+    #[test]
+    fn chat_log_format() {
+        let now = DateTime::from_unix(1_682_000_000);
+
+        let msg = privmsg("hello world");
+        assert_eq!(ChatLogger::format_text(&msg, &now), "[14:13:20] <Museun> hello world");
+
+        let msg = privmsg("\x01ACTION waves\x01");
+        assert_eq!(ChatLogger::format_text(&msg, &now), "[14:13:20] * Museun waves");
+
+        let json = ChatLogger::format_json(&msg, 1_682_000_000_000);
+        let json: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(json["data"], "waves");
+        assert_eq!(json["action"], true);
+        assert_eq!(json["sender"], "museun");
+        assert_eq!(json["tags"]["user-id"], "23196011");
+    }
+
+    #[tokio::test]
+    async fn chat_log_rotation() {
+        let dir = temp_dir("rotation");
+        let line_len = "[14:13:20] <Museun> hello\n".len() as u64;
+
+        // room for two lines per file
+        let mut logger = ChatLogger::new(&dir).with_max_size(line_len * 2);
+        for _ in 0..5 {
+            logger.log(&privmsg("hello")).await;
+        }
+
+        let channel = dir.join("shaken_bot");
+        let size = |name: &str| std::fs::metadata(channel.join(name)).map(|md| md.len()).ok();
+        assert_eq!(size("2023-04-20.log"), Some(line_len * 2));
+        assert_eq!(size("2023-04-20.1.log"), Some(line_len * 2));
+        assert_eq!(size("2023-04-20.2.log"), Some(line_len));
+
+        // after a restart, the same rule decides which file is used
+        drop(logger);
+        let mut logger = ChatLogger::new(&dir).with_max_size(line_len * 2);
+        logger.log(&privmsg("hello")).await;
+        logger.log(&privmsg("hello")).await;
+        assert_eq!(size("2023-04-20.2.log"), Some(line_len * 2));
+        assert_eq!(size("2023-04-20.3.log"), Some(line_len));
+
+        // a line larger than the max size still goes into an empty file
+        let mut logger = ChatLogger::new(&dir).with_max_size(1);
+        logger.log(&privmsg("hello")).await;
+        logger.log(&privmsg("hello")).await;
+        assert_eq!(size("2023-04-20.4.log"), Some(line_len));
+        assert_eq!(size("2023-04-20.5.log"), Some(line_len));
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn chat_log_compress_on_part() {
+        let dir = temp_dir("compress");
+
+        let mut logger = ChatLogger::new(&dir)
+            .with_format(LogFormat::JsonLines)
+            .with_compression(true);
+        logger.log(&privmsg("hello")).await;
+        logger.on_part("#shaken_bot").await;
+
+        let channel = dir.join("shaken_bot");
+        assert!(!channel.join("2023-04-20.jsonl").exists());
+        assert!(channel.join("2023-04-20.jsonl.gz").exists());
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
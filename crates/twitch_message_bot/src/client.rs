@@ -1,20 +1,31 @@
 use std::{
     collections::{HashSet, VecDeque},
+    future::Future,
+    sync::Arc,
     time::Duration,
 };
 
 use tokio::{
     io::{AsyncWrite, AsyncWriteExt},
     net::TcpStream,
-    sync::mpsc::UnboundedReceiver,
+    sync::{mpsc::UnboundedReceiver, Mutex},
 };
 use twitch_message::{
     encode::{Encodable, ALL_CAPABILITIES},
-    messages::{Privmsg, TwitchMessage},
+    messages::{Message, Privmsg, TwitchMessage, Whisper},
     Badge, Color, IntoStatic, PingTracker,
 };
 
-use crate::{writer::WriteKind, Config, Handler, Reconnect, Writer};
+use crate::{
+    echo::Echo,
+    intercept, middleware,
+    util::channel_name,
+    whisper::Whispers,
+    worker::{Delivery, Workers},
+    writer::{QueuedWrite, WriteKind},
+    ChannelEvent, ChannelStore, Config, ConnectionState, Handler, MiddlewareContext, Reconnect,
+    Writer,
+};
 
 #[non_exhaustive]
 #[derive(Debug)]
@@ -27,6 +38,15 @@ pub enum Error {
     },
 }
 
+impl Error {
+    /// Wraps an error from constructing a [`Handler`]
+    pub fn cannot_init(error: impl Into<Box<dyn std::error::Error + Send + Sync>>) -> Self {
+        Self::CannotInit {
+            error: error.into(),
+        }
+    }
+}
+
 impl std::fmt::Display for Error {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         let err = match self {
@@ -59,29 +79,36 @@ pub struct Identity {
 }
 
 pub struct Client<'a, H> {
-    pub(crate) handler: H,
+    pub(crate) handler: Arc<Mutex<H>>,
     pub(crate) buf: Vec<u8>,
 
-    recv: UnboundedReceiver<WriteKind>,
+    recv: UnboundedReceiver<QueuedWrite>,
     writer: Writer,
     channels: HashSet<Box<str>>,
-    queue: VecDeque<WriteKind>,
+    queue: VecDeque<QueuedWrite>,
+    whispers: Whispers,
+    workers: Workers<H>,
+    echo: Option<Echo>,
     config: &'a Config,
 }
 
 impl<'a, H: Handler> Client<'a, H> {
     pub fn new(
         handler: H,
-        recv: UnboundedReceiver<WriteKind>,
+        recv: UnboundedReceiver<QueuedWrite>,
         writer: Writer,
         config: &'a Config,
     ) -> Self {
+        let handler = Arc::new(Mutex::new(handler));
         Self {
+            workers: Workers::new(Arc::clone(&handler), writer.clone(), config.dispatch_mode),
             handler,
             recv,
             writer,
-            channels: HashSet::new(),
+            channels: config.channels.iter().map(|s| channel_name(s).into()).collect(),
             queue: VecDeque::new(),
+            whispers: Whispers::spawn(config),
+            echo: config.echo_own_messages.then(Echo::default),
             buf: Vec::with_capacity(1024),
             config,
         }
@@ -115,6 +142,12 @@ impl<'a, H: Handler> Client<'a, H> {
         let mut read = tokio::io::BufReader::new(read).lines();
         let pt = PingTracker::new(self.config.ping_delay);
         let mut our_name = <Option<String>>::None;
+        let mut our_id = <Option<String>>::None;
+        let mut joined = HashSet::<Box<str>>::new();
+
+        if let Some(echo) = &mut self.echo {
+            echo.clear();
+        }
 
         for channel in &self.channels {
             let msg = twitch_message::encode::join(channel);
@@ -191,12 +224,38 @@ impl<'a, H: Handler> Client<'a, H> {
                                     })
                                     .collect(),
                             };
-                            self.handler
-                                .on_connected(identity, self.writer.clone())
+                            self.whispers.set_user_id(&identity.user_id);
+                            our_id.replace(identity.user_id.clone());
+                            self.writer.update_status(|status| {
+                                status.state = ConnectionState::Registered;
+                                status.backoff = None;
+                                status.channels = 0;
+                            });
+
+                            self.workers
+                                .deliver("", Delivery::Connected(identity))
                                 .await;
 
-                            while let Some(msg) = self.queue.pop_front() {
-                                Self::handle_write(&mut write, &msg, &mut self.buf).await?;
+                            while let Some(QueuedWrite { kind: msg, delayed }) =
+                                self.queue.pop_front()
+                            {
+                                let interceptors = &self.config.interceptors;
+                                let Some(msg) = intercept::apply(interceptors, msg) else {
+                                    self.delayed_written(delayed);
+                                    continue;
+                                };
+                                Self::handle_write(
+                                    &mut write,
+                                    &msg,
+                                    &mut self.buf,
+                                    &self.whispers,
+                                )
+                                .await?;
+                                self.delayed_written(delayed);
+                                record_write(self.config, &msg).await;
+                                if let Some(echo) = &mut self.echo {
+                                    echo.sent(&msg);
+                                }
                                 if matches!(msg, WriteKind::Quit) {
                                     return Ok(());
                                 }
@@ -204,35 +263,109 @@ impl<'a, H: Handler> Client<'a, H> {
                         }
 
                         TwitchMessage::Join(msg) if our_name.as_deref() == Some(&*msg.user) => {
-                            self.handler.on_join(&msg.channel).await;
+                            self.channels.insert(channel_name(&msg.channel).into());
+                            joined.insert(channel_name(&msg.channel).into());
+                            self.writer.pending_joins().joined(&msg.channel);
+                            self.writer.update_status(|status| {
+                                status.state = ConnectionState::Joined;
+                                status.channels = joined.len();
+                            });
+                            self.workers
+                                .deliver(&msg.channel, Delivery::Join(Box::from(&*msg.channel)))
+                                .await;
                         }
 
                         TwitchMessage::Part(msg) if our_name.as_deref() == Some(&*msg.user) => {
-                            self.handler.on_part(&msg.channel).await;
+                            self.channels.remove(&*channel_name(&msg.channel));
+                            joined.remove(&*channel_name(&msg.channel));
+                            self.writer.update_status(|status| {
+                                status.state = match joined.is_empty() {
+                                    true => ConnectionState::Registered,
+                                    false => ConnectionState::Joined,
+                                };
+                                status.channels = joined.len();
+                            });
+                            self.workers
+                                .deliver(&msg.channel, Delivery::Part(Box::from(&*msg.channel)))
+                                .await;
+                            self.workers.remove(&msg.channel);
+                        }
+
+                        TwitchMessage::UserState(..) => {
+                            if let (Some(echo), Some(id), Some(name)) =
+                                (&mut self.echo, &our_id, &our_name)
+                            {
+                                if let Some(pm) = echo.user_state(&msg, id, name) {
+                                    self.workers
+                                        .deliver(&worker_key(&msg), Delivery::OwnPrivmsg(pm))
+                                        .await;
+                                }
+                            }
+                        }
+
+                        TwitchMessage::RoomState(msg) => {
+                            self.writer.pending_joins().room_state(&msg.channel);
+                        }
+
+                        TwitchMessage::Notice(..) => {
+                            self.writer.pending_joins().notice(&msg);
+                            if let Some(echo) = &mut self.echo {
+                                echo.notice(&msg);
+                            }
                         }
 
                         _ => {}
                     }
 
+                    let ctx = MiddlewareContext {
+                        our_name: our_name.as_deref(),
+                    };
+                    let Some(msg) = middleware::apply(&self.config.middleware, msg, &ctx) else {
+                        continue;
+                    };
+
+                    let channel = worker_key(&msg);
+
                     if let Some(pm) = msg.as_typed_message::<Privmsg>() {
-                        self.handler
-                            .on_privmsg(pm.clone(), self.writer.clone())
+                        self.workers
+                            .deliver(&channel, Delivery::Privmsg(pm.clone()))
+                            .await;
+                    };
+
+                    if let Some(whisper) = msg.as_typed_message::<Whisper>() {
+                        self.workers
+                            .deliver(&channel, Delivery::Whisper(whisper.clone()))
                             .await;
                     };
 
-                    self.handler
-                        .on_twitch_message(msg, self.writer.clone())
+                    if let Some(event) = ChannelEvent::from_message(&msg) {
+                        self.workers
+                            .deliver(&channel, Delivery::ChannelEvent(event))
+                            .await;
+                    }
+
+                    self.workers
+                        .deliver(&channel, Delivery::Message(msg))
                         .await;
                 }
 
-                Right(Some(kind)) if our_name.is_some() => {
-                    Self::handle_write(&mut write, &kind, &mut self.buf).await?;
+                Right(Some(QueuedWrite { kind, delayed })) if our_name.is_some() => {
+                    let Some(kind) = intercept::apply(&self.config.interceptors, kind) else {
+                        self.delayed_written(delayed);
+                        continue;
+                    };
+                    Self::handle_write(&mut write, &kind, &mut self.buf, &self.whispers).await?;
+                    self.delayed_written(delayed);
+                    record_write(self.config, &kind).await;
+                    if let Some(echo) = &mut self.echo {
+                        echo.sent(&kind);
+                    }
                     if matches!(kind, WriteKind::Quit) {
                         return Ok(());
                     }
                 }
 
-                Right(Some(kind)) => self.queue.push_back(kind),
+                Right(Some(queued)) => self.queue.push_back(queued),
 
                 Left(None) => {
                     log::warn!("cannot read from connection");
@@ -247,17 +380,37 @@ impl<'a, H: Handler> Client<'a, H> {
         }
     }
 
-    pub fn drain_pending_writes(&mut self) {
-        while let Ok(msg) = self.recv.try_recv() {
-            match msg {
+    pub async fn load_stored_channels(&mut self) {
+        let Some(store) = &self.config.channel_store else { return };
+        match with_store(store, |store| store.load()).await {
+            Ok(channels) => self.channels.extend(channels.iter().map(|s| channel_name(s).into())),
+            Err(err) => log::warn!("cannot load stored channels: {err}"),
+        }
+    }
+
+    /// Joins and parts made while disconnected are applied to the channels we'll (re)join
+    pub async fn drain_pending_writes(&mut self) {
+        while let Ok(queued) = self.recv.try_recv() {
+            match &queued.kind {
                 WriteKind::Join { channel } => {
-                    let _ = self.channels.insert(channel);
+                    let _ = self.channels.insert(channel_name(channel).into());
                 }
                 WriteKind::Part { channel } => {
-                    let _ = self.channels.remove(&*channel);
+                    let _ = self.channels.remove(&*channel_name(channel));
+                }
+                _ => {
+                    self.queue.push_back(queued);
+                    continue;
                 }
-                msg => self.queue.push_back(msg),
             }
+            record_write(self.config, &queued.kind).await;
+        }
+    }
+
+    /// A delayed message is only removed from its store once it has been written
+    fn delayed_written(&self, id: Option<u64>) {
+        if let Some(id) = id {
+            self.writer.delayed().written(id)
         }
     }
 
@@ -276,6 +429,7 @@ impl<'a, H: Handler> Client<'a, H> {
         conn: &mut (impl AsyncWrite + Send + Unpin),
         kind: &WriteKind,
         buf: &mut Vec<u8>,
+        whispers: &Whispers,
     ) -> Result<(), Error> {
         use twitch_message::encode::{join, part, privmsg, raw, reply};
         use WriteKind::*;
@@ -285,22 +439,69 @@ impl<'a, H: Handler> Client<'a, H> {
             Part { channel } => Self::write(conn, part(channel), buf).await,
             Raw { raw: msg } => Self::write(conn, raw(msg), buf).await,
             Privmsg { target, data } => {
-                for part in data.split('\n') {
-                    Self::write(conn, privmsg(target, part.trim()), buf).await?;
+                for part in crate::util::lines(data) {
+                    Self::write(conn, privmsg(target, part), buf).await?;
                 }
                 Ok(())
             }
             Reply { id, target, data } => {
-                for part in data.split('\n') {
-                    Self::write(conn, reply(id, target, part.trim()), buf).await?;
+                for part in crate::util::lines(data) {
+                    Self::write(conn, reply(id, target, part), buf).await?;
                 }
                 Ok(())
             }
+            Whisper { target, data } => {
+                whispers.send(target, data);
+                Ok(())
+            }
             Quit => Self::write(conn, QuitMessage, buf).await,
         }
     }
 }
 
+fn worker_key(msg: &Message<'_>) -> String {
+    match msg.args.first() {
+        Some(channel) if channel.starts_with('#') => channel.to_string(),
+        _ => String::new(),
+    }
+}
+
+/// Joins and parts are recorded once they are written (or applied while we're disconnected),
+/// not when Twitch echoes them back
+async fn record_write(config: &Config, kind: &WriteKind) {
+    let Some(store) = &config.channel_store else { return };
+    let (channel, join) = match kind {
+        WriteKind::Join { channel } => (channel_name(channel), true),
+        WriteKind::Part { channel } => (channel_name(channel), false),
+        _ => return,
+    };
+
+    let name = channel.clone();
+    let record = move |store: &dyn ChannelStore| {
+        if join {
+            store.join(&name)
+        } else {
+            store.part(&name)
+        }
+    };
+
+    if let Err(err) = with_store(store, record).await {
+        log::warn!("cannot update the channel store for {channel}: {err}");
+    }
+}
+
+/// Stores do blocking I/O, so they're used from the blocking thread pool. This is awaited so
+/// joins and parts are still recorded in the order they were written
+async fn with_store<T: Send + 'static>(
+    store: &Arc<dyn ChannelStore>,
+    f: impl FnOnce(&dyn ChannelStore) -> std::io::Result<T> + Send + 'static,
+) -> std::io::Result<T> {
+    let store = Arc::clone(store);
+    tokio::task::spawn_blocking(move || f(&*store))
+        .await
+        .map_err(|err| std::io::Error::new(std::io::ErrorKind::Other, err))?
+}
+
 struct QuitMessage;
 
 impl std::fmt::Display for QuitMessage {
@@ -320,46 +521,147 @@ impl twitch_message::encode::Formattable for QuitMessage {
     }
 }
 
+/// Passed to the init function of [`connect_with_init`]
+#[non_exhaustive]
+pub struct InitContext {
+    pub writer: Writer,
+}
+
 pub async fn connect<H: Handler>(config: Config) -> Result<(), crate::Error> {
-    const DEFAULT_DELAY: Duration = Duration::from_secs(10);
+    connect_with_init(config, |_, _| H::init()).await
+}
 
-    let (writer, recv) = Writer::new();
+/// Connect with an already constructed [`Handler`]
+pub async fn connect_with<H: Handler>(config: Config, handler: H) -> Result<(), crate::Error> {
+    connect_with_init(config, |_, _| async move { Ok(handler) }).await
+}
+
+/// Connect with a [`Handler`] built by `init`
+///
+/// `init` runs once, before the first connection is attempted
+pub async fn connect_with_init<H, F, Fut>(config: Config, init: F) -> Result<(), crate::Error>
+where
+    H: Handler,
+    F: FnOnce(&Config, InitContext) -> Fut,
+    Fut: Future<Output = Result<H, crate::Error>>,
+{
+    let (writer, recv) = Writer::for_config(&config);
+
+    let ctx = InitContext {
+        writer: writer.clone(),
+    };
+    let handler = init(&config, ctx).await?;
+    run_client(config, handler, writer, recv).await
+}
+
+pub(crate) async fn run_client<H: Handler>(
+    config: Config,
+    handler: H,
+    writer: Writer,
+    recv: UnboundedReceiver<QueuedWrite>,
+) -> Result<(), crate::Error> {
+    const DEFAULT_DELAY: Duration = Duration::from_secs(10);
 
-    let handler = H::init().await?;
     let mut client = Client::new(handler, recv, writer, &config);
+    client.load_stored_channels().await;
 
     loop {
-        client.handler.on_connecting().await;
-
-        let conn = match Client::<H>::connect(&config, &mut client.buf).await {
-            Ok(conn) => conn,
-            Err(error) => {
-                let delay = match client.handler.on_disconnected(error).await {
-                    Reconnect::Never => break,
-                    Reconnect::Always => DEFAULT_DELAY,
-                    Reconnect::After(delay) => delay,
-                };
-                log::debug!("waiting: {delay:.2?} to reconnect");
-                tokio::time::sleep(delay).await;
-                continue;
+        client.writer.update_status(|status| {
+            status.state = ConnectionState::Connecting;
+            status.backoff = None;
+            status.channels = 0;
+        });
+        client.handler.lock().await.on_connecting().await;
+
+        let error = match Client::<H>::connect(&config, &mut client.buf).await {
+            Ok(conn) => {
+                client.drain_pending_writes().await;
+                let result = client.run(conn).await;
+                // the joins that were in flight won't be confirmed on this connection
+                client.writer.pending_joins().disconnected();
+                match result {
+                    Ok(..) => break,
+                    Err(error) => error,
+                }
             }
+            Err(error) => error,
         };
 
-        client.drain_pending_writes();
-
-        match client.run(conn).await {
-            Ok(..) => break,
-            Err(error) => {
-                let delay = match client.handler.on_disconnected(error).await {
-                    Reconnect::Never => break,
-                    Reconnect::Always => DEFAULT_DELAY,
-                    Reconnect::After(delay) => delay,
-                };
-                log::debug!("waiting: {delay:.2?} to reconnect");
-                tokio::time::sleep(delay).await;
+        let last_error = error.to_string();
+        let delay = match client.handler.lock().await.on_disconnected(error).await {
+            Reconnect::Never => {
+                client.writer.update_status(|status| status.last_error = Some(last_error));
+                break;
             }
-        }
+            Reconnect::Always => DEFAULT_DELAY,
+            Reconnect::After(delay) => delay,
+        };
+
+        client.writer.update_status(|status| {
+            status.state = ConnectionState::WaitingToReconnect;
+            status.backoff = Some(delay);
+            status.last_error = Some(last_error);
+            status.channels = 0;
+        });
+        log::debug!("waiting: {delay:.2?} to reconnect");
+        tokio::time::sleep(delay).await;
     }
 
+    client.writer.pending_joins().disconnected();
+    client.writer.update_status(|status| {
+        status.state = ConnectionState::Stopped;
+        status.backoff = None;
+        status.channels = 0;
+    });
+
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::FileChannelStore;
+
+    struct Nop;
+
+    #[async_trait::async_trait]
+    impl Handler for Nop {
+        async fn on_connected<'a>(&'a mut self, _identity: Identity, _writer: Writer) {}
+
+        async fn on_privmsg<'a>(&'a mut self, _message: Privmsg<'static>, _writer: Writer) {}
+    }
+
+    #[tokio::test]
+    async fn offline_joins_and_parts_are_recorded() {
+        let path = std::env::temp_dir().join(format!(
+            "twitch_message_bot_offline_channels_{}",
+            std::process::id()
+        ));
+        let _ = std::fs::remove_file(&path);
+
+        let store = FileChannelStore::new(&path);
+        store.join("#shaken_bot").unwrap();
+
+        let config = Config::new("shaken_bot", "hunter2")
+            .with_channels(["museun"])
+            .with_channel_file(&path);
+        let (writer, recv) = Writer::for_config(&config);
+
+        let mut client = Client::new(Nop, recv, writer.clone(), &config);
+        client.load_stored_channels().await;
+        assert!(client.channels.contains("#shaken_bot"));
+
+        // museun is already in the channel set (from the config), it should still be recorded
+        writer.join_channel("Museun");
+        writer.part_channel("#shaken_bot");
+        writer.say("museun", "hello");
+        client.drain_pending_writes().await;
+
+        assert!(client.channels.contains("#museun"));
+        assert!(!client.channels.contains("#shaken_bot"));
+        assert_eq!(client.queue.len(), 1);
+        assert_eq!(store.load().unwrap(), ["#museun"]);
+
+        let _ = std::fs::remove_file(&path);
+    }
+}
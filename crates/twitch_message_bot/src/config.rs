@@ -1,25 +1,351 @@
-use std::time::Duration;
+use std::{sync::Arc, time::Duration};
+
+use crate::{
+    ChannelStore, DelayedStore, DispatchMode, FileChannelStore, Interceptor, Middleware,
+    ReplyPolicy,
+};
 
 #[non_exhaustive]
+#[cfg_attr(feature = "serde", derive(::serde::Deserialize))]
 pub struct Config {
     pub(crate) name: String,
+    #[cfg_attr(feature = "serde", serde(deserialize_with = "de::token"))]
     pub(crate) token: String,
+    #[cfg_attr(
+        feature = "serde",
+        serde(default = "default_ping_delay", deserialize_with = "de::seconds")
+    )]
     pub(crate) ping_delay: Duration,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub(crate) channels: Vec<String>,
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, rename = "channel_file", deserialize_with = "de::channel_file")
+    )]
+    pub(crate) channel_store: Option<Arc<dyn ChannelStore>>,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub(crate) delayed_store: Option<Arc<dyn DelayedStore>>,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub(crate) client_id: Option<String>,
+    #[cfg_attr(feature = "serde", serde(default = "default_helix_url"))]
+    pub(crate) helix_url: String,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub(crate) echo_own_messages: bool,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub(crate) dispatch_mode: DispatchMode,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub(crate) reply_policy: ReplyPolicy,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub(crate) middleware: Vec<Box<dyn Middleware>>,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub(crate) interceptors: Vec<Box<dyn Interceptor>>,
+}
+
+impl std::fmt::Debug for Config {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Config")
+            .field("name", &self.name)
+            .field("token", &"<redacted>")
+            .field("ping_delay", &self.ping_delay)
+            .field("channels", &self.channels)
+            .field("channel_store", &self.channel_store.is_some())
+            .field("delayed_store", &self.delayed_store.is_some())
+            .field("client_id", &self.client_id)
+            .field("helix_url", &self.helix_url)
+            .field("echo_own_messages", &self.echo_own_messages)
+            .field("dispatch_mode", &self.dispatch_mode)
+            .field("reply_policy", &self.reply_policy)
+            .field("middleware", &self.middleware.len())
+            .field("interceptors", &self.interceptors.len())
+            .finish()
+    }
 }
 
 impl Config {
+    pub const DEFAULT_HELIX_URL: &'static str = "https://api.twitch.tv/helix";
+
+    pub const ENV_NAME: &'static str = "TWITCH_NAME";
+    pub const ENV_TOKEN: &'static str = "TWITCH_TOKEN";
+    pub const ENV_CHANNELS: &'static str = "TWITCH_CHANNELS";
+    pub const ENV_CHANNEL_FILE: &'static str = "TWITCH_CHANNEL_FILE";
+    pub const ENV_PING_DELAY: &'static str = "TWITCH_PING_DELAY";
+    pub const ENV_CLIENT_ID: &'static str = "TWITCH_CLIENT_ID";
+    pub const ENV_HELIX_URL: &'static str = "TWITCH_HELIX_URL";
+
     pub fn new(name: impl ToString, token: impl ToString) -> Self {
         Self {
             name: name.to_string(),
-            token: token.to_string(),
-            ping_delay: Duration::from_secs(30),
+            token: normalize_token(&token.to_string()),
+            ping_delay: default_ping_delay(),
+            channels: Vec::new(),
+            channel_store: None,
+            delayed_store: None,
+            client_id: None,
+            helix_url: default_helix_url(),
+            echo_own_messages: false,
+            dispatch_mode: DispatchMode::default(),
+            reply_policy: ReplyPolicy::default(),
+            middleware: Vec::new(),
+            interceptors: Vec::new(),
         }
     }
 
+    /// Creates a config from the `TWITCH_*` environment variables
+    ///
+    /// `TWITCH_NAME` and `TWITCH_TOKEN` are required.
+    ///
+    /// `TWITCH_CHANNELS` is a comma separated list of channels to join,
+    /// `TWITCH_CHANNEL_FILE` is where joined channels are persisted,
+    /// `TWITCH_PING_DELAY` is in seconds
+    pub fn from_env() -> Result<Self, ConfigError> {
+        Self::from_vars(|key| std::env::var(key).ok())
+    }
+
+    fn from_vars(var: impl Fn(&str) -> Option<String>) -> Result<Self, ConfigError> {
+        let required = |key: &'static str| {
+            var(key)
+                .filter(|s| !s.trim().is_empty())
+                .ok_or(ConfigError::MissingVar { key })
+        };
+
+        let mut this = Self::new(required(Self::ENV_NAME)?, required(Self::ENV_TOKEN)?);
+
+        if let Some(channels) = var(Self::ENV_CHANNELS) {
+            let channels = channels.split(',').map(str::trim).filter(|s| !s.is_empty());
+            this = this.with_channels(channels);
+        }
+
+        if let Some(path) = var(Self::ENV_CHANNEL_FILE) {
+            this = this.with_channel_file(path);
+        }
+
+        if let Some(delay) = var(Self::ENV_PING_DELAY) {
+            let secs = delay
+                .trim()
+                .parse()
+                .map_err(|_| ConfigError::InvalidVar {
+                    key: Self::ENV_PING_DELAY,
+                    value: delay,
+                })?;
+            this = this.with_ping_delay(Duration::from_secs(secs));
+        }
+
+        if let Some(client_id) = var(Self::ENV_CLIENT_ID) {
+            this = this.with_client_id(client_id);
+        }
+
+        if let Some(helix_url) = var(Self::ENV_HELIX_URL) {
+            this = this.with_helix_url(helix_url);
+        }
+
+        Ok(this)
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn channels(&self) -> &[String] {
+        &self.channels
+    }
+
+    pub fn client_id(&self) -> Option<&str> {
+        self.client_id.as_deref()
+    }
+
+    pub fn ping_delay(&self) -> Duration {
+        self.ping_delay
+    }
+
     pub fn with_ping_delay(self, delay: impl Into<Duration>) -> Self {
         Self {
             ping_delay: delay.into(),
             ..self
         }
     }
+
+    /// Channels to join once connected
+    pub fn with_channels<I>(mut self, channels: I) -> Self
+    where
+        I: IntoIterator,
+        I::Item: ToString,
+    {
+        self.channels.extend(channels.into_iter().map(|s| s.to_string()));
+        self
+    }
+
+    /// Records joins and parts to this store, and rejoins its channels on startup
+    pub fn with_channel_store(self, store: impl ChannelStore) -> Self {
+        Self {
+            channel_store: Some(Arc::new(store)),
+            ..self
+        }
+    }
+
+    /// Use a [`FileChannelStore`] at this path
+    pub fn with_channel_file(self, path: impl Into<std::path::PathBuf>) -> Self {
+        self.with_channel_store(FileChannelStore::new(path))
+    }
+
+    /// Where messages from [`Writer::send_at`](crate::Writer::send_at) are kept until they are sent
+    pub fn with_delayed_store(self, store: impl DelayedStore) -> Self {
+        Self {
+            delayed_store: Some(Arc::new(store)),
+            ..self
+        }
+    }
+
+    /// The client id the token was issued for. This is required for sending whispers
+    pub fn with_client_id(self, client_id: impl ToString) -> Self {
+        Self {
+            client_id: Some(client_id.to_string()),
+            ..self
+        }
+    }
+
+    /// Use a different base url for the Helix api (e.g. a local mock server)
+    pub fn with_helix_url(self, helix_url: impl ToString) -> Self {
+        Self {
+            helix_url: helix_url.to_string(),
+            ..self
+        }
+    }
+
+    /// Once Twitch accepts a message we sent, deliver it to
+    /// [`Handler::on_own_privmsg`](crate::Handler::on_own_privmsg)
+    pub fn with_echo_own_messages(self, echo_own_messages: bool) -> Self {
+        Self {
+            echo_own_messages,
+            ..self
+        }
+    }
+
+    pub fn with_dispatch_mode(self, dispatch_mode: DispatchMode) -> Self {
+        Self {
+            dispatch_mode,
+            ..self
+        }
+    }
+
+    pub fn with_reply_policy(self, reply_policy: ReplyPolicy) -> Self {
+        Self {
+            reply_policy,
+            ..self
+        }
+    }
+
+    /// Adds a [`Middleware`] to the end of the incoming message pipeline
+    pub fn with_middleware(mut self, middleware: impl Middleware) -> Self {
+        self.middleware.push(Box::new(middleware));
+        self
+    }
+
+    /// Adds an [`Interceptor`] to the end of the outgoing message pipeline
+    pub fn with_interceptor(mut self, interceptor: impl Interceptor) -> Self {
+        self.interceptors.push(Box::new(interceptor));
+        self
+    }
+}
+
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum ConfigError {
+    MissingVar { key: &'static str },
+    InvalidVar { key: &'static str, value: String },
+}
+
+impl std::fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::MissingVar { key } => write!(f, "Missing environment variable: {key}"),
+            Self::InvalidVar { key, value } => {
+                write!(f, "Invalid value for environment variable {key}: {value}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+fn default_ping_delay() -> Duration {
+    Duration::from_secs(30)
+}
+
+fn default_helix_url() -> String {
+    Config::DEFAULT_HELIX_URL.to_string()
+}
+
+fn normalize_token(token: &str) -> String {
+    let token = token.trim();
+    let token = token.strip_prefix("oauth:").unwrap_or(token);
+    format!("oauth:{token}")
+}
+
+#[cfg(feature = "serde")]
+mod de {
+    use std::{path::PathBuf, time::Duration};
+
+    use ::serde::{Deserialize as _, Deserializer};
+
+    use crate::{ChannelStore, FileChannelStore};
+
+    pub fn token<'de, D: Deserializer<'de>>(deser: D) -> Result<String, D::Error> {
+        String::deserialize(deser).map(|s| super::normalize_token(&s))
+    }
+
+    pub fn seconds<'de, D: Deserializer<'de>>(deser: D) -> Result<Duration, D::Error> {
+        u64::deserialize(deser).map(Duration::from_secs)
+    }
+
+    pub fn channel_file<'de, D: Deserializer<'de>>(
+        deser: D,
+    ) -> Result<Option<Box<dyn ChannelStore>>, D::Error> {
+        <Option<PathBuf>>::deserialize(deser)
+            .map(|path| path.map(|path| Box::new(FileChannelStore::new(path)) as _))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn config_from_vars() {
+        let vars = [
+            ("TWITCH_NAME", "shaken_bot"),
+            ("TWITCH_TOKEN", "hunter2"),
+            ("TWITCH_CHANNELS", "museun, #shaken_bot,"),
+            ("TWITCH_PING_DELAY", "10"),
+        ];
+        let var = |key: &str| vars.iter().find_map(|(k, v)| (*k == key).then(|| v.to_string()));
+
+        let config = Config::from_vars(var).unwrap();
+        assert_eq!(config.name, "shaken_bot");
+        assert_eq!(config.token, "oauth:hunter2");
+        assert_eq!(config.channels, ["museun", "#shaken_bot"]);
+        assert_eq!(config.ping_delay, Duration::from_secs(10));
+        assert!(!format!("{config:?}").contains("hunter2"));
+
+        let err = Config::from_vars(|_| None).unwrap_err();
+        assert!(matches!(err, ConfigError::MissingVar { key: "TWITCH_NAME" }));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn config_deserialize() {
+        let config: Config = serde_yaml::from_str(
+            r#"
+            name: shaken_bot
+            token: oauth:hunter2
+            ping_delay: 15
+            channels: [museun]
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(config.token, "oauth:hunter2");
+        assert_eq!(config.ping_delay, Duration::from_secs(15));
+        assert_eq!(config.channels, ["museun"]);
+        assert_eq!(config.helix_url, Config::DEFAULT_HELIX_URL);
+    }
 }
@@ -0,0 +1,283 @@
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::{Duration, SystemTime},
+};
+
+use tokio::{sync::mpsc::UnboundedSender, task::JoinHandle};
+
+use crate::{writer::QueuedWrite, WriteKind};
+
+/// A message that will be sent to a channel later, see [`Writer::send_at`](crate::Writer::send_at)
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+#[non_exhaustive]
+pub struct DelayedMessage {
+    pub id: u64,
+    pub channel: String,
+    pub data: String,
+    pub at: SystemTime,
+}
+
+/// Persists [`DelayedMessage`]s so they are still sent after a restart
+pub trait DelayedStore: Send + Sync + 'static {
+    fn load(&self) -> std::io::Result<Vec<DelayedMessage>>;
+    fn insert(&self, message: &DelayedMessage) -> std::io::Result<()>;
+    fn remove(&self, id: u64) -> std::io::Result<()>;
+}
+
+/// A [`DelayedStore`] that keeps the pending messages as a JSON array in a file
+#[cfg(feature = "serde")]
+pub struct JsonDelayedStore {
+    path: std::path::PathBuf,
+    lock: parking_lot::Mutex<()>,
+}
+
+#[cfg(feature = "serde")]
+impl JsonDelayedStore {
+    pub fn new(path: impl Into<std::path::PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            lock: parking_lot::Mutex::new(()),
+        }
+    }
+
+    fn read(&self) -> std::io::Result<Vec<DelayedMessage>> {
+        match std::fs::read(&self.path) {
+            Ok(data) => serde_json::from_slice(&data).map_err(Into::into),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(Vec::new()),
+            Err(err) => Err(err),
+        }
+    }
+
+    fn update(&self, update: impl FnOnce(&mut Vec<DelayedMessage>)) -> std::io::Result<()> {
+        let _guard = self.lock.lock();
+        let mut messages = self.read()?;
+        update(&mut messages);
+        std::fs::write(&self.path, serde_json::to_vec_pretty(&messages)?)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl DelayedStore for JsonDelayedStore {
+    fn load(&self) -> std::io::Result<Vec<DelayedMessage>> {
+        let _guard = self.lock.lock();
+        self.read()
+    }
+
+    fn insert(&self, message: &DelayedMessage) -> std::io::Result<()> {
+        self.update(|messages| messages.push(message.clone()))
+    }
+
+    fn remove(&self, id: u64) -> std::io::Result<()> {
+        self.update(|messages| messages.retain(|msg| msg.id != id))
+    }
+}
+
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum DelayedError {
+    OutOfRange { delay: Duration },
+}
+
+impl std::fmt::Display for DelayedError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::OutOfRange { delay } => write!(f, "Delay is too long: {delay:?}"),
+        }
+    }
+}
+
+impl std::error::Error for DelayedError {}
+
+/// A pending delayed message, it can be cancelled until it has been sent
+#[derive(Clone)]
+pub struct DelayedHandle {
+    id: u64,
+    delayed: Arc<Delayed>,
+}
+
+impl DelayedHandle {
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    /// Returns false if the message was already sent or cancelled
+    pub fn cancel(&self) -> bool {
+        self.delayed.cancel(self.id)
+    }
+
+    pub fn is_pending(&self) -> bool {
+        self.delayed.tasks.lock().contains_key(&self.id)
+    }
+}
+
+#[derive(Default)]
+pub(crate) struct Delayed {
+    next_id: AtomicU64,
+    store: Option<Arc<dyn DelayedStore>>,
+    tasks: parking_lot::Mutex<HashMap<u64, JoinHandle<()>>>,
+}
+
+impl Delayed {
+    /// Starts the timers for the messages that were pending when we last stopped
+    pub(crate) fn restore(
+        store: Arc<dyn DelayedStore>,
+        sender: &UnboundedSender<QueuedWrite>,
+    ) -> Arc<Self> {
+        let pending = store.load().unwrap_or_else(|err| {
+            log::warn!("cannot load the delayed messages: {err}");
+            Vec::new()
+        });
+
+        let this = Arc::new(Self {
+            next_id: AtomicU64::new(pending.iter().map(|msg| msg.id + 1).max().unwrap_or(0)),
+            store: Some(store),
+            tasks: Default::default(),
+        });
+
+        for message in pending {
+            this.spawn(message, sender.clone());
+        }
+        this
+    }
+
+    pub(crate) fn send_at(
+        self: &Arc<Self>,
+        channel: Box<str>,
+        data: String,
+        at: SystemTime,
+        sender: &UnboundedSender<QueuedWrite>,
+    ) -> DelayedHandle {
+        let message = DelayedMessage {
+            id: self.next_id.fetch_add(1, Ordering::Relaxed),
+            channel: channel.into(),
+            data,
+            at,
+        };
+
+        if let Some(store) = &self.store {
+            if let Err(err) = store.insert(&message) {
+                log::warn!("cannot store delayed message {}: {err}", message.id);
+            }
+        }
+
+        let id = message.id;
+        self.spawn(message, sender.clone());
+        DelayedHandle {
+            id,
+            delayed: Arc::clone(self),
+        }
+    }
+
+    fn spawn(self: &Arc<Self>, message: DelayedMessage, sender: UnboundedSender<QueuedWrite>) {
+        // hold the lock so the task can't finish before it is inserted
+        let mut tasks = self.tasks.lock();
+
+        let this = Arc::clone(self);
+        let id = message.id;
+        let task = tokio::spawn(async move {
+            let wait = message.at.duration_since(SystemTime::now()).unwrap_or_default();
+            tokio::time::sleep(wait).await;
+
+            // sending under the lock means a message is either cancelled or sent, never both
+            let mut tasks = this.tasks.lock();
+            if tasks.remove(&message.id).is_none() {
+                return;
+            }
+
+            // the client removes it from the store once it has been written
+            let _ = sender.send(QueuedWrite {
+                kind: WriteKind::Privmsg {
+                    target: message.channel.into(),
+                    data: message.data.into(),
+                },
+                delayed: Some(message.id),
+            });
+        });
+
+        tasks.insert(id, task);
+    }
+
+    fn cancel(&self, id: u64) -> bool {
+        let Some(task) = self.tasks.lock().remove(&id) else {
+            return false;
+        };
+        task.abort();
+        self.forget(id);
+        true
+    }
+
+    /// The message was written (or dropped by an interceptor), so it no longer has to be restored
+    pub(crate) fn written(&self, id: u64) {
+        self.forget(id)
+    }
+
+    fn forget(&self, id: u64) {
+        if let Some(store) = &self.store {
+            if let Err(err) = store.remove(id) {
+                log::warn!("cannot remove delayed message {id}: {err}");
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Default)]
+    struct MemoryStore(parking_lot::Mutex<Vec<DelayedMessage>>);
+
+    impl DelayedStore for MemoryStore {
+        fn load(&self) -> std::io::Result<Vec<DelayedMessage>> {
+            Ok(self.0.lock().clone())
+        }
+
+        fn insert(&self, message: &DelayedMessage) -> std::io::Result<()> {
+            self.0.lock().push(message.clone());
+            Ok(())
+        }
+
+        fn remove(&self, id: u64) -> std::io::Result<()> {
+            self.0.lock().retain(|msg| msg.id != id);
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn delayed_kept_until_written() {
+        let store = Arc::new(MemoryStore::default());
+        let (sender, mut recv) = tokio::sync::mpsc::unbounded_channel();
+        let delayed = Delayed::restore(Arc::clone(&store) as _, &sender);
+
+        let now = SystemTime::now();
+        let handle = delayed.send_at("#museun".into(), "hello".into(), now, &sender);
+        let cancelled = delayed.send_at("#museun".into(), "world".into(), now, &sender);
+        assert!(cancelled.cancel());
+        assert!(!cancelled.cancel());
+
+        let queued = recv.recv().await.unwrap();
+        assert!(matches!(&queued.kind, WriteKind::Privmsg { data, .. } if &**data == "hello"));
+        assert_eq!(queued.delayed, Some(handle.id()));
+        assert!(!handle.is_pending());
+        assert!(!handle.cancel());
+
+        // sent, but not written yet
+        assert_eq!(store.load().unwrap().len(), 1);
+
+        delayed.written(handle.id());
+        assert!(store.load().unwrap().is_empty());
+        assert!(recv.try_recv().is_err());
+    }
+
+    #[test]
+    fn delayed_out_of_range() {
+        let (writer, _recv) = crate::Writer::new();
+        let err = writer.send_after("museun", "hello", Duration::MAX).unwrap_err();
+        assert!(matches!(err, DelayedError::OutOfRange { .. }));
+    }
+}
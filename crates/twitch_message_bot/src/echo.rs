@@ -0,0 +1,161 @@
+use std::collections::{HashMap, VecDeque};
+
+use twitch_message::{
+    builders::TagsBuilder,
+    messages::{types::MsgId, Message, Privmsg},
+};
+
+use crate::{util::channel_login, WriteKind};
+
+// these are the USERSTATE tags that also appear on a PRIVMSG
+const COPIED_TAGS: &[&str] = &[
+    "badge-info",
+    "badges",
+    "color",
+    "display-name",
+    "mod",
+    "subscriber",
+    "turbo",
+    "user-type",
+];
+
+// the NOTICEs Twitch sends instead of a USERSTATE when it rejects a PRIVMSG
+// https://dev.twitch.tv/docs/irc/msg-id/
+const REJECTED: &[&str] = &[
+    "msg_banned",
+    "msg_bad_characters",
+    "msg_channel_blocked",
+    "msg_duplicate",
+    "msg_emoteonly",
+    "msg_followersonly",
+    "msg_followersonly_followed",
+    "msg_followersonly_zero",
+    "msg_r9k",
+    "msg_ratelimit",
+    "msg_rejected",
+    "msg_rejected_mandatory",
+    "msg_requires_verified_phone_number",
+    "msg_slowmode",
+    "msg_subsonly",
+    "msg_suspended",
+    "msg_timedout",
+    "msg_verified_email",
+];
+
+struct Pending {
+    data: Box<str>,
+    reply: Option<MsgId>,
+}
+
+/// Turns our own messages into [`Privmsg`]s once Twitch acknowledges them
+///
+/// Twitch doesn't echo our messages back to us, but it does send a USERSTATE
+/// (with the id it assigned to the message) for each one we send
+#[derive(Default)]
+pub(crate) struct Echo {
+    pending: HashMap<Box<str>, VecDeque<Pending>>,
+}
+
+impl Echo {
+    pub(crate) fn sent(&mut self, kind: &WriteKind) {
+        let (target, data, reply) = match kind {
+            WriteKind::Privmsg { target, data } => (target, data, None),
+            WriteKind::Reply { id, target, data } => (target, data, Some(id)),
+            _ => return,
+        };
+
+        let pending = self.pending.entry(channel_login(target).into()).or_default();
+        // each line is sent as its own message
+        pending.extend(crate::util::lines(data).map(|line| Pending {
+            data: line.into(),
+            reply: reply.cloned(),
+        }));
+    }
+
+    pub(crate) fn user_state(
+        &mut self,
+        msg: &Message<'_>,
+        user_id: &str,
+        name: &str,
+    ) -> Option<Privmsg<'static>> {
+        let channel = msg.args.first()?;
+        // Twitch also sends a USERSTATE after we join a channel, only the ones for
+        // our messages have an id
+        let id = msg.tags.get("id")?;
+        let Pending { data, reply } = self.pending.get_mut(&*channel_login(channel))?.pop_front()?;
+
+        let mut tags = TagsBuilder::default();
+        for &key in COPIED_TAGS {
+            if let Some(val) = msg.tags.get(key) {
+                tags = tags.add(key, &*val);
+            }
+        }
+
+        tags = tags.add("id", &*id);
+
+        if let Some(reply) = &reply {
+            tags = tags.add("reply-parent-msg-id", reply.as_str());
+        }
+
+        let ts = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_millis())
+            .unwrap_or_default();
+
+        let tags = tags
+            .add("user-id", user_id)
+            .add("tmi-sent-ts", &ts.to_string())
+            .finish();
+
+        Privmsg::builder()
+            .channel(&**channel)
+            .sender(name)
+            .data(&*data)
+            .tags(tags)
+            .finish_privmsg()
+            .ok()
+    }
+
+    pub(crate) fn notice(&mut self, msg: &Message<'_>) {
+        // a rejected message (slow mode, duplicate, banned, ...) won't get a USERSTATE
+        let Some(channel) = msg.args.first() else { return };
+        if matches!(msg.tags.get("msg-id"), Some(id) if REJECTED.contains(&&*id)) {
+            if let Some(pending) = self.pending.get_mut(&*channel_login(channel)) {
+                pending.pop_front();
+            }
+        }
+    }
+
+    pub(crate) fn clear(&mut self) {
+        self.pending.clear()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(line: &str) -> Message<'_> {
+        twitch_message::parse(line).unwrap().message
+    }
+
+    #[test]
+    fn echo_rejected_notice() {
+        let mut echo = Echo::default();
+        echo.sent(&WriteKind::Privmsg {
+            target: "#museun".into(),
+            data: "first\nsecond".into(),
+        });
+
+        // not about a message we sent
+        echo.notice(&parse("@msg-id=msg_channel_suspended :tmi.twitch.tv NOTICE #museun :x\r\n"));
+        echo.notice(&parse("@msg-id=slow_on :tmi.twitch.tv NOTICE #museun :slow mode\r\n"));
+        // the first line was rejected
+        echo.notice(&parse("@msg-id=msg_slowmode :tmi.twitch.tv NOTICE #museun :slow\r\n"));
+
+        let state = parse("@id=abc;mod=0 :tmi.twitch.tv USERSTATE #museun\r\n");
+        let pm = echo.user_state(&state, "12345", "shaken_bot").unwrap();
+        assert_eq!(pm.data, "second");
+        assert!(echo.user_state(&state, "12345", "shaken_bot").is_none());
+    }
+}
@@ -0,0 +1,141 @@
+use std::{
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use tokio::sync::mpsc::{UnboundedReceiver, UnboundedSender};
+use twitch_message::messages::{Message, Privmsg, Whisper};
+
+use crate::{ChannelEvent, Config, Error, Handler, Identity, Reconnect, Writer};
+
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum Event {
+    /// The first connection attempt is starting
+    Connecting,
+    /// A connection attempt after a disconnect is starting
+    Reconnecting,
+    Connected { identity: Identity },
+    Disconnected { error: Error },
+    Join { channel: String },
+    Part { channel: String },
+    Privmsg(Privmsg<'static>),
+    /// A message we sent, see [`Config::with_echo_own_messages`]
+    OwnPrivmsg(Privmsg<'static>),
+    Whisper(Whisper<'static>),
+    ChannelEvent(ChannelEvent),
+    /// Every message received,
+    /// including the ones also sent as [`Event::Privmsg`] and [`Event::Whisper`]
+    Message(Message<'static>),
+}
+
+/// A stream of [`Event`]s produced by [`events`]
+///
+/// This ends once the connection has been closed with [`Writer::quit`]
+pub struct Events {
+    pub(crate) recv: UnboundedReceiver<Event>,
+}
+
+impl Events {
+    pub async fn next(&mut self) -> Option<Event> {
+        self.recv.recv().await
+    }
+}
+
+impl futures_core::Stream for Events {
+    type Item = Event;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.recv.poll_recv(cx)
+    }
+}
+
+/// Connect without a [`Handler`], returning a [`Writer`] and a stream of [`Event`]s
+///
+/// The connection is driven by a spawned task. It stops after [`Writer::quit`] is called,
+/// and it won't reconnect once the [`Events`] have been dropped
+pub fn events(config: Config) -> (Writer, Events) {
+    let (writer, recv) = Writer::for_config(&config);
+    let (send, events) = tokio::sync::mpsc::unbounded_channel();
+
+    let forward = Forward {
+        send,
+        connected: false,
+    };
+
+    tokio::spawn({
+        let writer = writer.clone();
+        async move {
+            if let Err(err) = crate::client::run_client(config, forward, writer, recv).await {
+                log::warn!("event stream stopped: {err}")
+            }
+        }
+    });
+
+    (writer, Events { recv: events })
+}
+
+struct Forward {
+    send: UnboundedSender<Event>,
+    connected: bool,
+}
+
+impl Forward {
+    fn send(&self, event: Event) {
+        let _ = self.send.send(event);
+    }
+}
+
+#[async_trait::async_trait]
+impl Handler for Forward {
+    async fn on_connected<'a>(&'a mut self, identity: Identity, _writer: Writer) {
+        self.connected = true;
+        self.send(Event::Connected { identity })
+    }
+
+    async fn on_connecting<'a>(&'a mut self) {
+        match self.connected {
+            true => self.send(Event::Reconnecting),
+            false => self.send(Event::Connecting),
+        }
+    }
+
+    async fn on_disconnected<'a>(&'a mut self, error: Error) -> Reconnect {
+        if self.send.send(Event::Disconnected { error }).is_err() {
+            return Reconnect::Never;
+        }
+        Reconnect::Always
+    }
+
+    async fn on_twitch_message<'a>(&'a mut self, message: Message<'static>, _writer: Writer) {
+        self.send(Event::Message(message))
+    }
+
+    async fn on_privmsg<'a>(&'a mut self, message: Privmsg<'static>, _writer: Writer) {
+        self.send(Event::Privmsg(message))
+    }
+
+    async fn on_own_privmsg<'a>(&'a mut self, message: Privmsg<'static>, _writer: Writer) {
+        self.send(Event::OwnPrivmsg(message))
+    }
+
+    async fn on_whisper<'a>(&'a mut self, message: Whisper<'static>, _writer: Writer) {
+        self.send(Event::Whisper(message))
+    }
+
+    async fn on_channel_event<'a>(&'a mut self, event: ChannelEvent, _writer: Writer) {
+        self.send(Event::ChannelEvent(event))
+    }
+
+    async fn on_join<'a, 'b>(&'a mut self, channel: &'b str) {
+        self.send(Event::Join {
+            channel: channel.to_string(),
+        })
+    }
+
+    async fn on_part<'a, 'b>(&'a mut self, channel: &'b str) {
+        self.send(Event::Part {
+            channel: channel.to_string(),
+        })
+    }
+}
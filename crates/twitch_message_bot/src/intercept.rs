@@ -0,0 +1,278 @@
+use std::{
+    borrow::Cow,
+    collections::{HashMap, HashSet},
+    sync::Arc,
+};
+
+use crate::{util::channel_login, WriteKind};
+
+/// A step that runs on every outgoing message before it is written to the connection
+pub trait Interceptor: Send + Sync + 'static {
+    fn intercept(&self, kind: WriteKind) -> Intercept;
+}
+
+impl<F> Interceptor for F
+where
+    F: Fn(WriteKind) -> Intercept,
+    F: Send + Sync + 'static,
+{
+    fn intercept(&self, kind: WriteKind) -> Intercept {
+        (self)(kind)
+    }
+}
+
+pub enum Intercept {
+    Continue(WriteKind),
+    Drop { reason: Cow<'static, str> },
+}
+
+impl Intercept {
+    pub fn drop(reason: impl Into<Cow<'static, str>>) -> Self {
+        Self::Drop {
+            reason: reason.into(),
+        }
+    }
+}
+
+pub(crate) fn apply(interceptors: &[Box<dyn Interceptor>], kind: WriteKind) -> Option<WriteKind> {
+    let mut kind = kind;
+    for interceptor in interceptors {
+        kind = match interceptor.intercept(kind) {
+            Intercept::Continue(kind) => kind,
+            Intercept::Drop { reason } => {
+                log::debug!("dropping outgoing message: {reason}");
+                return None;
+            }
+        };
+    }
+    Some(kind)
+}
+
+/// Replaces any of these words in outgoing messages
+///
+/// Only whole words are replaced, so banning `ass` leaves `class` alone
+pub struct BannedWords {
+    words: Vec<String>,
+    replacement: String,
+}
+
+impl BannedWords {
+    pub fn new<I>(words: I) -> Self
+    where
+        I: IntoIterator,
+        I::Item: ToString,
+    {
+        Self {
+            words: words
+                .into_iter()
+                .map(|s| s.to_string().to_ascii_lowercase())
+                .filter(|s| !s.is_empty())
+                .collect(),
+            replacement: String::from("***"),
+        }
+    }
+
+    pub fn with_replacement(self, replacement: impl ToString) -> Self {
+        Self {
+            replacement: replacement.to_string(),
+            ..self
+        }
+    }
+
+    fn censor(&self, data: &str) -> Option<String> {
+        let is_word = |c: char| c.is_alphanumeric() || c == '_';
+
+        let mut out = data.to_string();
+        let mut changed = false;
+
+        for word in &self.words {
+            let mut start = 0;
+            while let Some(pos) = out[start..].to_ascii_lowercase().find(word.as_str()) {
+                let pos = start + pos;
+                let end = pos + word.len();
+
+                let inside_word = out[..pos].chars().next_back().map_or(false, is_word)
+                    || out[end..].chars().next().map_or(false, is_word);
+                if inside_word {
+                    start = pos + out[pos..].chars().next().map_or(1, char::len_utf8);
+                    continue;
+                }
+
+                out.replace_range(pos..end, &self.replacement);
+                start = pos + self.replacement.len();
+                changed = true;
+            }
+        }
+
+        changed.then_some(out)
+    }
+}
+
+impl Interceptor for BannedWords {
+    fn intercept(&self, mut kind: WriteKind) -> Intercept {
+        if let Some(data) = kind.data_mut() {
+            if let Some(censored) = self.censor(data) {
+                *data = censored.into();
+            }
+        }
+        Intercept::Continue(kind)
+    }
+}
+
+/// Drops messages sent to channels that are in "quiet mode"
+///
+/// This can be cloned and the channels can be changed at runtime
+#[derive(Clone, Default)]
+pub struct QuietChannels {
+    channels: Arc<parking_lot::RwLock<HashSet<String>>>,
+}
+
+impl QuietChannels {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn quiet(&self, channel: &str) {
+        self.channels.write().insert(channel_login(channel));
+    }
+
+    pub fn unquiet(&self, channel: &str) {
+        self.channels.write().remove(&channel_login(channel));
+    }
+
+    pub fn is_quiet(&self, channel: &str) -> bool {
+        self.channels.read().contains(&channel_login(channel))
+    }
+}
+
+impl Interceptor for QuietChannels {
+    fn intercept(&self, kind: WriteKind) -> Intercept {
+        let quiet = kind
+            .channel()
+            .filter(|channel| kind.data().is_some() && self.is_quiet(channel))
+            .map(ToString::to_string);
+
+        match quiet {
+            Some(channel) => Intercept::drop(format!("{channel} is in quiet mode")),
+            None => Intercept::Continue(kind),
+        }
+    }
+}
+
+/// Prefixes every message sent to a channel
+#[derive(Default)]
+pub struct PrefixChannels {
+    prefixes: HashMap<String, String>,
+}
+
+impl PrefixChannels {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with(mut self, channel: &str, prefix: impl ToString) -> Self {
+        self.prefixes.insert(channel_login(channel), prefix.to_string());
+        self
+    }
+}
+
+impl Interceptor for PrefixChannels {
+    fn intercept(&self, mut kind: WriteKind) -> Intercept {
+        let Some(prefix) = kind.channel().and_then(|c| self.prefixes.get(&channel_login(c))) else {
+            return Intercept::Continue(kind);
+        };
+
+        let prefix = prefix.clone();
+        if let Some(data) = kind.data_mut() {
+            *data = format!("{prefix}{data}").into();
+        }
+        Intercept::Continue(kind)
+    }
+}
+
+/// Logs every outgoing message at the provided level
+pub struct LogWrites(pub log::Level);
+
+impl Interceptor for LogWrites {
+    fn intercept(&self, kind: WriteKind) -> Intercept {
+        log::log!(self.0, "{}", kind.to_string().trim_end());
+        Intercept::Continue(kind)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn privmsg(target: &str, data: &str) -> WriteKind {
+        WriteKind::Privmsg {
+            target: target.into(),
+            data: data.into(),
+        }
+    }
+
+    #[test]
+    fn banned_words_censor() {
+        let banned = BannedWords::new(["ass", "Bad Word"]);
+
+        for (input, expected) in [
+            ("this is fine", None),
+            ("a class of bass players", None),
+            ("you ass", Some("you ***")),
+            ("ASS! ass, (ass) assassin", Some("***! ***, (***) assassin")),
+            ("a bad word here", Some("a *** here")),
+            ("a bad wordsmith", None),
+            ("émoji ass émoji", Some("émoji *** émoji")),
+        ] {
+            assert_eq!(banned.censor(input).as_deref(), expected, "{input}");
+        }
+
+        let banned = banned.with_replacement("[removed]");
+        assert_eq!(banned.censor("ass").as_deref(), Some("[removed]"));
+    }
+
+    #[test]
+    fn quiet_channels() {
+        let quiet = QuietChannels::new();
+        quiet.quiet("#Museun");
+        assert!(quiet.is_quiet("museun"));
+        assert!(quiet.is_quiet("#museun"));
+
+        let interceptor = quiet.clone();
+        assert!(matches!(
+            interceptor.intercept(privmsg("#museun", "hello")),
+            Intercept::Drop { .. }
+        ));
+        assert!(matches!(
+            interceptor.intercept(privmsg("#shaken_bot", "hello")),
+            Intercept::Continue(..)
+        ));
+
+        // joins and parts aren't chat messages
+        let join = WriteKind::Join {
+            channel: "#museun".into(),
+        };
+        assert!(matches!(interceptor.intercept(join), Intercept::Continue(..)));
+
+        quiet.unquiet("museun");
+        assert!(matches!(
+            interceptor.intercept(privmsg("#museun", "hello")),
+            Intercept::Continue(..)
+        ));
+    }
+
+    #[test]
+    fn prefix_channels() {
+        let prefix = PrefixChannels::new().with("#Museun", "[bot] ");
+
+        let Intercept::Continue(kind) = prefix.intercept(privmsg("#museun", "hello")) else {
+            panic!("message should not be dropped")
+        };
+        assert_eq!(kind.data(), Some("[bot] hello"));
+
+        let Intercept::Continue(kind) = prefix.intercept(privmsg("#shaken_bot", "hello")) else {
+            panic!("message should not be dropped")
+        };
+        assert_eq!(kind.data(), Some("hello"));
+    }
+}
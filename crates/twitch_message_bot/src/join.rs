@@ -0,0 +1,124 @@
+use std::collections::HashMap;
+
+use tokio::sync::oneshot;
+use twitch_message::messages::Message;
+
+use crate::util::channel_login;
+
+/// Why [`Writer::join_channel_confirmed`](crate::Writer::join_channel_confirmed) failed
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum JoinError {
+    /// Twitch didn't confirm the join in time
+    Timeout,
+    ChannelSuspended,
+    /// We are banned from the channel
+    Banned,
+    /// The connection was closed before the join was confirmed
+    Disconnected,
+    /// Twitch rejected the join with some other NOTICE
+    Notice { msg_id: String, message: String },
+}
+
+impl std::fmt::Display for JoinError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Timeout => f.write_str("Timed out waiting to join the channel"),
+            Self::ChannelSuspended => f.write_str("The channel is suspended"),
+            Self::Banned => f.write_str("We are banned from the channel"),
+            Self::Disconnected => f.write_str("Disconnected before the channel was joined"),
+            Self::Notice { msg_id, message } => {
+                write!(f, "Cannot join the channel ({msg_id}): {message}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for JoinError {}
+
+#[derive(Default)]
+struct Pending {
+    joined: bool,
+    room_state: bool,
+    waiters: Vec<oneshot::Sender<Result<(), JoinError>>>,
+}
+
+/// Joins that are waiting for our JOIN echo and the channel's ROOMSTATE
+#[derive(Default)]
+pub(crate) struct PendingJoins {
+    pending: parking_lot::Mutex<HashMap<Box<str>, Pending>>,
+}
+
+impl PendingJoins {
+    pub(crate) fn wait(&self, channel: &str) -> oneshot::Receiver<Result<(), JoinError>> {
+        let (tx, rx) = oneshot::channel();
+        let mut pending = self.pending.lock();
+        let pending = pending.entry(channel_login(channel).into()).or_default();
+        pending.waiters.retain(|tx| !tx.is_closed());
+        pending.waiters.push(tx);
+        rx
+    }
+
+    pub(crate) fn joined(&self, channel: &str) {
+        self.update(channel, |p| p.joined = true)
+    }
+
+    pub(crate) fn room_state(&self, channel: &str) {
+        self.update(channel, |p| p.room_state = true)
+    }
+
+    pub(crate) fn notice(&self, msg: &Message<'_>) {
+        let Some(channel) = msg.args.first() else { return };
+        let Some(msg_id) = msg.tags.get("msg-id") else { return };
+
+        let error = match &*msg_id {
+            "msg_channel_suspended" => JoinError::ChannelSuspended,
+            "msg_banned" | "tos_ban" => JoinError::Banned,
+            "msg_room_not_found" | "msg_channel_blocked" => JoinError::Notice {
+                msg_id: msg_id.to_string(),
+                message: msg.data.as_deref().unwrap_or_default().to_string(),
+            },
+            _ => return,
+        };
+
+        if let Some(pending) = self.pending.lock().remove(&*channel_login(channel)) {
+            for tx in pending.waiters {
+                let _ = tx.send(Err(error.clone()));
+            }
+        }
+    }
+
+    /// Forgets about `channel` if nothing is waiting on it anymore
+    pub(crate) fn prune(&self, channel: &str) {
+        let key: Box<str> = channel_login(channel).into();
+        let mut map = self.pending.lock();
+        let Some(pending) = map.get_mut(&key) else { return };
+
+        pending.waiters.retain(|tx| !tx.is_closed());
+        if pending.waiters.is_empty() {
+            map.remove(&key);
+        }
+    }
+
+    /// Fails everything that is still waiting, this is called whenever the connection is lost
+    pub(crate) fn disconnected(&self) {
+        for (_, pending) in self.pending.lock().drain() {
+            for tx in pending.waiters {
+                let _ = tx.send(Err(JoinError::Disconnected));
+            }
+        }
+    }
+
+    fn update(&self, channel: &str, update: impl FnOnce(&mut Pending)) {
+        let key: Box<str> = channel_login(channel).into();
+        let mut map = self.pending.lock();
+        let Some(pending) = map.get_mut(&key) else { return };
+
+        update(pending);
+        if pending.joined && pending.room_state {
+            for tx in map.remove(&key).into_iter().flat_map(|p| p.waiters) {
+                let _ = tx.send(Ok(()));
+            }
+        }
+    }
+}
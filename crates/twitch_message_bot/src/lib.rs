@@ -1,7 +1,7 @@
 // #![cfg_attr(debug_assertions, allow(dead_code, unused_variables,))]
 use std::time::Duration;
 
-use twitch_message::messages::{Message, Privmsg};
+use twitch_message::messages::{Message, Privmsg, Whisper};
 
 // struct Bot;
 
@@ -29,9 +29,18 @@ use twitch_message::messages::{Message, Privmsg};
 
 #[async_trait::async_trait]
 pub trait Handler: Send + Sync + 'static {
+    /// Creates the handler for [`Handler::connect`] and [`connect`]
+    ///
+    /// Handlers that are only used with [`connect_with`] or [`connect_with_init`] don't need
+    /// this, by default it returns [`Error::CannotInit`]
     async fn init() -> Result<Self, crate::Error>
     where
-        Self: Sized;
+        Self: Sized,
+    {
+        Err(Error::cannot_init(
+            "this handler has no `init`, create it with `connect_with` or `connect_with_init`",
+        ))
+    }
 
     async fn connect(config: Config) -> Result<(), Error>
     where
@@ -51,6 +60,20 @@ pub trait Handler: Send + Sync + 'static {
         let _writer = writer;
     }
     async fn on_privmsg<'a>(&'a mut self, message: Privmsg<'static>, writer: Writer);
+    /// A message we sent, see [`Config::with_echo_own_messages`]
+    async fn on_own_privmsg<'a>(&'a mut self, message: Privmsg<'static>, writer: Writer) {
+        let _message = message;
+        let _writer = writer;
+    }
+    async fn on_whisper<'a>(&'a mut self, message: Whisper<'static>, writer: Writer) {
+        let _message = message;
+        let _writer = writer;
+    }
+    /// A sub, gift, raid, cheer, announcement or ritual
+    async fn on_channel_event<'a>(&'a mut self, event: ChannelEvent, writer: Writer) {
+        let _event = event;
+        let _writer = writer;
+    }
     async fn on_join<'a, 'b>(&'a mut self, channel: &'b str) {
         let _channel = channel;
     }
@@ -66,17 +89,58 @@ pub enum Reconnect {
 }
 
 mod config;
-pub use config::Config;
+pub use config::{Config, ConfigError};
+
+mod channel_store;
+pub use channel_store::{ChannelStore, FileChannelStore};
+
+mod state;
+pub use state::{ConnectionState, ConnectionStatus};
+
+mod delayed;
+#[cfg(feature = "serde")]
+pub use delayed::JsonDelayedStore;
+pub use delayed::{DelayedError, DelayedHandle, DelayedMessage, DelayedStore};
+
+mod join;
+pub use join::JoinError;
 
 mod writer;
-#[doc(hidden)]
-pub use writer::WriteKind;
-pub use writer::Writer;
+pub use writer::{ChannelWriter, QueuedWrite, ReplyError, ReplyPolicy, WriteKind, Writer};
 
 mod client;
-pub use client::{Error, Identity};
+pub use client::{connect, connect_with, connect_with_init, Error, Identity, InitContext};
+
+mod channel_event;
+pub use channel_event::{ChannelEvent, ChannelEventKind, SubPlan};
+
+mod plugin;
+pub use plugin::{Plugin, PluginHandle, PluginId, Plugins};
+
+pub mod chat_log;
+pub use chat_log::ChatLogger;
+
+pub mod schedule;
+pub use schedule::{Schedule, Scheduler};
+
+mod events;
+pub use events::{events, Event, Events};
+
+mod multi;
+pub use multi::{connect_many, MultiHandler, MultiWriter};
+
+mod worker;
+pub use worker::DispatchMode;
+
+pub mod middleware;
+pub use middleware::{Middleware, MiddlewareContext};
+
+pub mod intercept;
+pub use intercept::{Intercept, Interceptor};
 
+mod echo;
 mod util;
+mod whisper;
 
 /// Re-exports
 pub use async_trait::async_trait;
@@ -0,0 +1,196 @@
+use std::collections::HashSet;
+
+use twitch_message::messages::{Message, Privmsg};
+
+/// A step that runs on every incoming message
+/// before it is delivered to the [`Handler`](crate::Handler)
+///
+/// Returning `None` stops the message from reaching the handler (and any middleware after this one)
+pub trait Middleware: Send + Sync + 'static {
+    fn process(
+        &self,
+        message: Message<'static>,
+        ctx: &MiddlewareContext<'_>,
+    ) -> Option<Message<'static>>;
+}
+
+impl<F> Middleware for F
+where
+    F: Fn(Message<'static>, &MiddlewareContext<'_>) -> Option<Message<'static>>,
+    F: Send + Sync + 'static,
+{
+    fn process(
+        &self,
+        message: Message<'static>,
+        ctx: &MiddlewareContext<'_>,
+    ) -> Option<Message<'static>> {
+        (self)(message, ctx)
+    }
+}
+
+#[non_exhaustive]
+pub struct MiddlewareContext<'a> {
+    pub our_name: Option<&'a str>,
+}
+
+pub(crate) fn apply(
+    middleware: &[Box<dyn Middleware>],
+    message: Message<'static>,
+    ctx: &MiddlewareContext<'_>,
+) -> Option<Message<'static>> {
+    middleware
+        .iter()
+        .try_fold(message, |message, middleware| middleware.process(message, ctx))
+}
+
+/// Drops messages from any of these users
+pub struct IgnoreUsers {
+    users: HashSet<String>,
+}
+
+impl IgnoreUsers {
+    pub fn new<I>(users: I) -> Self
+    where
+        I: IntoIterator,
+        I::Item: ToString,
+    {
+        Self {
+            users: users
+                .into_iter()
+                .map(|s| s.to_string().to_ascii_lowercase())
+                .collect(),
+        }
+    }
+}
+
+impl Middleware for IgnoreUsers {
+    fn process(
+        &self,
+        message: Message<'static>,
+        _ctx: &MiddlewareContext<'_>,
+    ) -> Option<Message<'static>> {
+        match message.as_typed_message::<Privmsg>() {
+            Some(pm) if self.users.contains(&pm.sender.to_ascii_lowercase()) => None,
+            _ => Some(message),
+        }
+    }
+}
+
+/// Drops messages that we sent
+pub struct IgnoreSelf;
+
+impl Middleware for IgnoreSelf {
+    fn process(
+        &self,
+        message: Message<'static>,
+        ctx: &MiddlewareContext<'_>,
+    ) -> Option<Message<'static>> {
+        match (message.as_typed_message::<Privmsg>(), ctx.our_name) {
+            (Some(pm), Some(name)) if pm.sender.eq_ignore_ascii_case(name) => None,
+            _ => Some(message),
+        }
+    }
+}
+
+/// Logs every message at the provided level
+pub struct LogMessages(pub log::Level);
+
+impl Middleware for LogMessages {
+    fn process(
+        &self,
+        message: Message<'static>,
+        _ctx: &MiddlewareContext<'_>,
+    ) -> Option<Message<'static>> {
+        log::log!(self.0, "{}", message.raw.trim_end());
+        Some(message)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    };
+
+    use twitch_message::IntoStatic as _;
+
+    use super::*;
+
+    fn privmsg(sender: &str, data: &str) -> Message<'static> {
+        let line = format!(":{sender}!{sender}@{sender}.tmi.twitch.tv PRIVMSG #museun :{data}\r\n");
+        twitch_message::parse(&line).unwrap().message.into_static()
+    }
+
+    fn ping() -> Message<'static> {
+        twitch_message::parse("PING :tmi.twitch.tv\r\n")
+            .unwrap()
+            .message
+            .into_static()
+    }
+
+    const CTX: MiddlewareContext<'static> = MiddlewareContext {
+        our_name: Some("shaken_bot"),
+    };
+
+    #[test]
+    fn ignore_users() {
+        let ignore = IgnoreUsers::new(["Nightbot", "streamelements"]);
+
+        assert!(ignore.process(privmsg("nightbot", "hello"), &CTX).is_none());
+        assert!(ignore.process(privmsg("StreamElements", "hello"), &CTX).is_none());
+        assert!(ignore.process(privmsg("museun", "hello"), &CTX).is_some());
+        // only chat messages are ignored
+        assert!(ignore.process(ping(), &CTX).is_some());
+    }
+
+    #[test]
+    fn ignore_self() {
+        assert!(IgnoreSelf.process(privmsg("Shaken_Bot", "hello"), &CTX).is_none());
+        assert!(IgnoreSelf.process(privmsg("museun", "hello"), &CTX).is_some());
+        assert!(IgnoreSelf.process(ping(), &CTX).is_some());
+
+        // before we know our name, nothing is ignored
+        let ctx = MiddlewareContext { our_name: None };
+        assert!(IgnoreSelf.process(privmsg("shaken_bot", "hello"), &ctx).is_some());
+    }
+
+    #[test]
+    fn log_messages() {
+        let msg = LogMessages(log::Level::Trace)
+            .process(privmsg("museun", "hello"), &CTX)
+            .unwrap();
+        assert_eq!(msg.data.as_deref(), Some("hello"));
+    }
+
+    #[test]
+    fn middleware_order() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let counter = |calls: &Arc<AtomicUsize>| {
+            let calls = Arc::clone(calls);
+            move |msg: Message<'static>, _: &MiddlewareContext<'_>| {
+                calls.fetch_add(1, Ordering::SeqCst);
+                Some(msg)
+            }
+        };
+
+        let middleware: Vec<Box<dyn Middleware>> = vec![
+            Box::new(counter(&calls)),
+            Box::new(IgnoreUsers::new(["nightbot"])),
+            // each step sees what the one before it returned
+            Box::new(|msg: Message<'static>, _: &MiddlewareContext<'_>| {
+                let data = msg.data.as_deref().unwrap_or_default();
+                Some(privmsg("museun", &format!("{data} world")))
+            }),
+            Box::new(counter(&calls)),
+        ];
+
+        let msg = apply(&middleware, privmsg("museun", "hello"), &CTX).unwrap();
+        assert_eq!(msg.data.as_deref(), Some("hello world"));
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+
+        // nothing after a dropped message runs
+        assert!(apply(&middleware, privmsg("nightbot", "hello"), &CTX).is_none());
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+    }
+}
@@ -0,0 +1,166 @@
+use std::{collections::HashMap, sync::Arc};
+
+use crate::{events, Config, Event, Events, Writer};
+
+/// Receives the [`Event`]s of every account started with [`connect_many`]
+#[async_trait::async_trait]
+pub trait MultiHandler: Send + Sync + 'static {
+    /// `account` is the name from the [`Config`] of the account that received the event
+    async fn on_event<'a, 'b>(&'a mut self, account: &'b str, event: Event, writer: MultiWriter);
+}
+
+/// A [`Writer`] for each account started with [`connect_many`]
+#[derive(Clone)]
+pub struct MultiWriter {
+    writers: Arc<HashMap<Box<str>, Writer>>,
+}
+
+impl MultiWriter {
+    /// The [`Writer`] that sends as `account`
+    pub fn account(&self, account: &str) -> Option<&Writer> {
+        self.writers.get(&*account.to_ascii_lowercase())
+    }
+
+    pub fn accounts(&self) -> impl Iterator<Item = &str> + '_ {
+        self.writers.keys().map(|s| &**s)
+    }
+
+    /// Send a message to a channel as `account`, returns false if there is no such account
+    pub fn say_as(&self, account: &str, channel: &str, data: impl ToString) -> bool {
+        match self.account(account) {
+            Some(writer) => {
+                writer.say(channel, data);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Quits every account
+    pub fn quit(&self) {
+        self.writers.values().for_each(Writer::quit)
+    }
+}
+
+/// Connect several accounts at once, delivering all of their events to one [`MultiHandler`]
+///
+/// This returns once every account has quit. Connection errors don't stop an account, they are
+/// delivered as [`Event::Disconnected`] and the account reconnects
+pub async fn connect_many<H: MultiHandler>(configs: impl IntoIterator<Item = Config>, handler: H) {
+    let mut accounts = Vec::new();
+    for config in configs {
+        let account: Box<str> = config.name.to_ascii_lowercase().into();
+        if accounts.iter().any(|(name, ..)| *name == account) {
+            log::warn!("'{account}' was configured more than once, ignoring the duplicate");
+            continue;
+        }
+
+        let (writer, events) = events(config);
+        accounts.push((account, writer, events));
+    }
+
+    run_many(accounts, handler).await
+}
+
+async fn run_many<H: MultiHandler>(accounts: Vec<(Box<str>, Writer, Events)>, mut handler: H) {
+    let (send, mut recv) = tokio::sync::mpsc::unbounded_channel();
+    let mut writers = HashMap::new();
+
+    for (account, writer, mut events) in accounts {
+        writers.insert(account.clone(), writer);
+
+        let send = send.clone();
+        tokio::spawn(async move {
+            while let Some(event) = events.next().await {
+                if send.send((account.clone(), event)).is_err() {
+                    break;
+                }
+            }
+        });
+    }
+    drop(send);
+
+    let writer = MultiWriter {
+        writers: Arc::new(writers),
+    };
+
+    while let Some((account, event)) = recv.recv().await {
+        handler.on_event(&account, event, writer.clone()).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::sync::mpsc::{UnboundedReceiver, UnboundedSender};
+
+    use super::*;
+    use crate::QueuedWrite;
+
+    type Seen = Arc<parking_lot::Mutex<Vec<String>>>;
+
+    /// Answers a join on one account from the other
+    struct Relay(Seen);
+
+    #[async_trait::async_trait]
+    impl MultiHandler for Relay {
+        async fn on_event<'a, 'b>(
+            &'a mut self,
+            account: &'b str,
+            event: Event,
+            writer: MultiWriter,
+        ) {
+            let Event::Join { channel } = event else { return };
+            self.0.lock().push(format!("{account}:{channel}"));
+
+            let other = if account == "museun" { "SHAKEN_BOT" } else { "museun" };
+            assert!(writer.say_as(other, &channel, format!("{account} joined")));
+            assert!(!writer.say_as("unknown", &channel, "hello"));
+        }
+    }
+
+    type Account = (Box<str>, Writer, Events);
+
+    /// An account, what it receives and what it writes
+    fn account(name: &str) -> (Account, UnboundedSender<Event>, UnboundedReceiver<QueuedWrite>) {
+        let (writer, writes) = Writer::new();
+        let (send, recv) = tokio::sync::mpsc::unbounded_channel();
+        ((name.into(), writer, Events { recv }), send, writes)
+    }
+
+    fn join(channel: &str) -> Event {
+        Event::Join {
+            channel: channel.to_string(),
+        }
+    }
+
+    fn said(writes: &mut UnboundedReceiver<QueuedWrite>) -> Option<String> {
+        let kind = writes.try_recv().ok()?.kind;
+        Some(format!("{} {}", kind.channel()?, kind.data()?))
+    }
+
+    #[tokio::test]
+    async fn multi_routing() {
+        let (first, museun, mut museun_writes) = account("museun");
+        let (second, shaken_bot, mut shaken_bot_writes) = account("shaken_bot");
+
+        let seen = Seen::default();
+        museun.send(join("#museun")).unwrap();
+        shaken_bot.send(join("#shaken_bot")).unwrap();
+        drop((museun, shaken_bot));
+
+        // returns once every account's events have ended
+        run_many(vec![first, second], Relay(Arc::clone(&seen))).await;
+
+        let mut seen = std::mem::take(&mut *seen.lock());
+        seen.sort();
+        assert_eq!(seen, ["museun:#museun", "shaken_bot:#shaken_bot"]);
+
+        let said_by_museun = said(&mut museun_writes);
+        assert_eq!(said_by_museun.as_deref(), Some("#shaken_bot shaken_bot joined"));
+        assert_eq!(said(&mut museun_writes), None);
+
+        let said_by_shaken_bot = said(&mut shaken_bot_writes);
+        assert_eq!(said_by_shaken_bot.as_deref(), Some("#museun museun joined"));
+        assert_eq!(said(&mut shaken_bot_writes), None);
+    }
+}
@@ -0,0 +1,389 @@
+use std::{
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use twitch_message::messages::{Message, Privmsg, Whisper};
+
+use crate::{ChannelEvent, Error, Handler, Identity, Reconnect, Writer};
+
+/// A piece of a bot that receives every [`Handler`] callback through [`Plugins`]
+#[async_trait::async_trait]
+pub trait Plugin: Send + Sync + 'static {
+    async fn on_connecting<'a>(&'a mut self) {}
+
+    async fn on_connected<'a, 'b>(&'a mut self, identity: &'b Identity, writer: Writer) {
+        let _identity = identity;
+        let _writer = writer;
+    }
+
+    async fn on_disconnected<'a, 'b>(&'a mut self, error: &'b Error) -> Reconnect {
+        let _error = error;
+        Reconnect::Always
+    }
+
+    async fn on_twitch_message<'a, 'b>(
+        &'a mut self,
+        message: &'b Message<'static>,
+        writer: Writer,
+    ) {
+        let _message = message;
+        let _writer = writer;
+    }
+
+    async fn on_privmsg<'a, 'b>(&'a mut self, message: &'b Privmsg<'static>, writer: Writer) {
+        let _message = message;
+        let _writer = writer;
+    }
+
+    async fn on_own_privmsg<'a, 'b>(&'a mut self, message: &'b Privmsg<'static>, writer: Writer) {
+        let _message = message;
+        let _writer = writer;
+    }
+
+    async fn on_whisper<'a, 'b>(&'a mut self, message: &'b Whisper<'static>, writer: Writer) {
+        let _message = message;
+        let _writer = writer;
+    }
+
+    async fn on_channel_event<'a, 'b>(&'a mut self, event: &'b ChannelEvent, writer: Writer) {
+        let _event = event;
+        let _writer = writer;
+    }
+
+    async fn on_join<'a, 'b>(&'a mut self, channel: &'b str) {
+        let _channel = channel;
+    }
+
+    async fn on_part<'a, 'b>(&'a mut self, channel: &'b str) {
+        let _channel = channel;
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct PluginId(u64);
+
+enum Op {
+    Add(PluginId, Box<dyn Plugin>),
+    Remove(PluginId),
+}
+
+/// Adds and removes plugins from a running [`Plugins`]
+#[derive(Clone)]
+pub struct PluginHandle {
+    pending: Arc<parking_lot::Mutex<Vec<Op>>>,
+    next_id: Arc<AtomicU64>,
+}
+
+impl PluginHandle {
+    /// The plugin is added before the next callback is delivered
+    pub fn add(&self, plugin: impl Plugin) -> PluginId {
+        let id = PluginId(self.next_id.fetch_add(1, Ordering::Relaxed));
+        self.pending.lock().push(Op::Add(id, Box::new(plugin)));
+        id
+    }
+
+    /// The plugin is removed before the next callback is delivered
+    pub fn remove(&self, id: PluginId) {
+        self.pending.lock().push(Op::Remove(id));
+    }
+}
+
+/// A [`Handler`] that sends every callback to each of its [`Plugin`]s, in the order they were added
+pub struct Plugins {
+    plugins: Vec<(PluginId, Box<dyn Plugin>)>,
+    handle: PluginHandle,
+    connected: Option<(Identity, Writer)>,
+}
+
+impl Default for Plugins {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Plugins {
+    pub fn new() -> Self {
+        Self {
+            plugins: Vec::new(),
+            handle: PluginHandle {
+                pending: Arc::default(),
+                next_id: Arc::default(),
+            },
+            connected: None,
+        }
+    }
+
+    pub fn with(mut self, plugin: impl Plugin) -> Self {
+        self.add(plugin);
+        self
+    }
+
+    pub fn add(&mut self, plugin: impl Plugin) -> PluginId {
+        self.handle.add(plugin)
+    }
+
+    pub fn remove(&mut self, id: PluginId) {
+        self.handle.remove(id)
+    }
+
+    pub fn handle(&self) -> PluginHandle {
+        self.handle.clone()
+    }
+
+    pub fn len(&self) -> usize {
+        self.plugins.len() + self.handle.pending.lock().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    async fn apply_pending(&mut self) {
+        let pending = std::mem::take(&mut *self.handle.pending.lock());
+        for op in pending {
+            match op {
+                Op::Add(id, mut plugin) => {
+                    // plugins added while connected still need to know who we are
+                    if let Some((identity, writer)) = &self.connected {
+                        plugin.on_connected(identity, writer.clone()).await;
+                    }
+                    self.plugins.push((id, plugin));
+                }
+                Op::Remove(id) => self.plugins.retain(|(k, _)| *k != id),
+            }
+        }
+    }
+
+    fn plugins(&mut self) -> impl Iterator<Item = &mut Box<dyn Plugin>> {
+        self.plugins.iter_mut().map(|(_, plugin)| plugin)
+    }
+}
+
+#[async_trait::async_trait]
+impl Handler for Plugins {
+    async fn init() -> Result<Self, Error> {
+        Ok(Self::new())
+    }
+
+    async fn on_connected<'a>(&'a mut self, identity: Identity, writer: Writer) {
+        self.connected = None;
+        self.apply_pending().await;
+
+        for plugin in self.plugins() {
+            plugin.on_connected(&identity, writer.clone()).await;
+        }
+        self.connected = Some((identity, writer));
+    }
+
+    async fn on_connecting<'a>(&'a mut self) {
+        self.apply_pending().await;
+        for plugin in self.plugins() {
+            plugin.on_connecting().await;
+        }
+    }
+
+    async fn on_disconnected<'a>(&'a mut self, error: Error) -> Reconnect {
+        self.connected = None;
+        self.apply_pending().await;
+
+        let mut reconnect = Reconnect::Always;
+        for plugin in self.plugins() {
+            reconnect = match (reconnect, plugin.on_disconnected(&error).await) {
+                (Reconnect::Never, ..) | (.., Reconnect::Never) => Reconnect::Never,
+                (Reconnect::After(left), Reconnect::After(right)) => {
+                    Reconnect::After(Duration::max(left, right))
+                }
+                (Reconnect::After(delay), ..) | (.., Reconnect::After(delay)) => {
+                    Reconnect::After(delay)
+                }
+                _ => Reconnect::Always,
+            };
+        }
+        reconnect
+    }
+
+    async fn on_twitch_message<'a>(&'a mut self, message: Message<'static>, writer: Writer) {
+        self.apply_pending().await;
+        for plugin in self.plugins() {
+            plugin.on_twitch_message(&message, writer.clone()).await;
+        }
+    }
+
+    async fn on_privmsg<'a>(&'a mut self, message: Privmsg<'static>, writer: Writer) {
+        self.apply_pending().await;
+        for plugin in self.plugins() {
+            plugin.on_privmsg(&message, writer.clone()).await;
+        }
+    }
+
+    async fn on_own_privmsg<'a>(&'a mut self, message: Privmsg<'static>, writer: Writer) {
+        self.apply_pending().await;
+        for plugin in self.plugins() {
+            plugin.on_own_privmsg(&message, writer.clone()).await;
+        }
+    }
+
+    async fn on_whisper<'a>(&'a mut self, message: Whisper<'static>, writer: Writer) {
+        self.apply_pending().await;
+        for plugin in self.plugins() {
+            plugin.on_whisper(&message, writer.clone()).await;
+        }
+    }
+
+    async fn on_channel_event<'a>(&'a mut self, event: ChannelEvent, writer: Writer) {
+        self.apply_pending().await;
+        for plugin in self.plugins() {
+            plugin.on_channel_event(&event, writer.clone()).await;
+        }
+    }
+
+    async fn on_join<'a, 'b>(&'a mut self, channel: &'b str) {
+        self.apply_pending().await;
+        for plugin in self.plugins() {
+            plugin.on_join(channel).await;
+        }
+    }
+
+    async fn on_part<'a, 'b>(&'a mut self, channel: &'b str) {
+        self.apply_pending().await;
+        for plugin in self.plugins() {
+            plugin.on_part(channel).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use twitch_message::IntoStatic as _;
+
+    use super::*;
+
+    type Calls = Arc<parking_lot::Mutex<Vec<String>>>;
+
+    /// Records each callback as `name:callback`
+    struct Record {
+        name: &'static str,
+        calls: Calls,
+        reconnect: fn() -> Reconnect,
+    }
+
+    impl Record {
+        fn new(name: &'static str, calls: &Calls) -> Self {
+            Self {
+                name,
+                calls: Arc::clone(calls),
+                reconnect: || Reconnect::Always,
+            }
+        }
+
+        fn record(&self, callback: &str) {
+            self.calls.lock().push(format!("{}:{callback}", self.name))
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl Plugin for Record {
+        async fn on_connected<'a, 'b>(&'a mut self, _identity: &'b Identity, _writer: Writer) {
+            self.record("connected")
+        }
+
+        async fn on_disconnected<'a, 'b>(&'a mut self, _error: &'b Error) -> Reconnect {
+            self.record("disconnected");
+            (self.reconnect)()
+        }
+
+        async fn on_privmsg<'a, 'b>(&'a mut self, message: &'b Privmsg<'static>, _writer: Writer) {
+            self.record(&message.data)
+        }
+
+        async fn on_join<'a, 'b>(&'a mut self, channel: &'b str) {
+            self.record(channel)
+        }
+    }
+
+    fn identity() -> Identity {
+        Identity {
+            user_id: String::from("12345"),
+            name: String::from("shaken_bot"),
+            display_name: None,
+            color: None,
+            emote_sets: vec![],
+            global_badges: vec![],
+        }
+    }
+
+    fn privmsg(data: &str) -> Privmsg<'static> {
+        let line = format!(":museun!museun@museun.tmi.twitch.tv PRIVMSG #museun :{data}\r\n");
+        let msg = twitch_message::parse(&line).unwrap().message.into_static();
+        msg.as_typed_message::<Privmsg>().unwrap().clone()
+    }
+
+    fn take(calls: &Calls) -> Vec<String> {
+        std::mem::take(&mut *calls.lock())
+    }
+
+    #[tokio::test]
+    async fn plugin_order() {
+        let calls = Calls::default();
+        let (writer, _recv) = Writer::new();
+
+        let mut plugins = Plugins::new()
+            .with(Record::new("a", &calls))
+            .with(Record::new("b", &calls));
+        let handle = plugins.handle();
+
+        plugins.on_connected(identity(), writer.clone()).await;
+        plugins.on_join("#museun").await;
+        plugins.on_privmsg(privmsg("hello"), writer.clone()).await;
+        assert_eq!(
+            take(&calls),
+            [
+                "a:connected",
+                "b:connected",
+                "a:#museun",
+                "b:#museun",
+                "a:hello",
+                "b:hello",
+            ]
+        );
+
+        // a plugin added while connected is told about the connection first, and goes last
+        let c = handle.add(Record::new("c", &calls));
+        plugins.on_privmsg(privmsg("world"), writer.clone()).await;
+        assert_eq!(take(&calls), ["c:connected", "a:world", "b:world", "c:world"]);
+
+        let a = PluginId(0);
+        handle.remove(a);
+        handle.remove(c);
+        plugins.on_privmsg(privmsg("again"), writer.clone()).await;
+        assert_eq!(take(&calls), ["b:again"]);
+        assert_eq!(plugins.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn plugin_reconnect() {
+        let calls = Calls::default();
+        let plugin = |reconnect: fn() -> Reconnect| Record {
+            reconnect,
+            ..Record::new("p", &calls)
+        };
+
+        let mut plugins = Plugins::new()
+            .with(plugin(|| Reconnect::After(Duration::from_secs(5))))
+            .with(plugin(|| Reconnect::Always))
+            .with(plugin(|| Reconnect::After(Duration::from_secs(10))));
+        let reconnect = plugins.on_disconnected(Error::CannotRead).await;
+        assert!(matches!(reconnect, Reconnect::After(d) if d == Duration::from_secs(10)));
+
+        // every plugin is told, even after one of them said to stop
+        plugins.add(plugin(|| Reconnect::Never));
+        plugins.add(plugin(|| Reconnect::Always));
+        let reconnect = plugins.on_disconnected(Error::CannotRead).await;
+        assert!(matches!(reconnect, Reconnect::Never));
+        assert_eq!(take(&calls).len(), 3 + 5);
+    }
+}
@@ -0,0 +1,699 @@
+use std::{
+    collections::HashSet,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use twitch_message::messages::Privmsg;
+
+use crate::{
+    util::{channel_name, DateTime},
+    ConnectionState, Error, Identity, Plugin, Reconnect, Writer,
+};
+
+/// When a [`Schedule`] posts
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(::serde::Serialize, ::serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
+#[non_exhaustive]
+pub enum When {
+    /// Every `n` seconds, starting once we're connected
+    Every(#[cfg_attr(feature = "serde", serde(with = "secs"))] Duration),
+    /// Whenever the [`Cron`] expression matches the current minute (in UTC)
+    Cron(Cron),
+}
+
+/// A message that is sent to a channel on a schedule
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+#[non_exhaustive]
+pub struct Schedule {
+    pub name: String,
+    pub channel: String,
+    pub message: String,
+    pub when: When,
+    /// How many chat lines there must be since the last post
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub min_lines: usize,
+    /// A random delay of up to this long is added to each post
+    #[cfg_attr(feature = "serde", serde(default, with = "secs"))]
+    pub jitter: Duration,
+    #[cfg_attr(feature = "serde", serde(default = "enabled"))]
+    pub enabled: bool,
+}
+
+impl Schedule {
+    pub fn every(name: &str, channel: &str, message: &str, interval: Duration) -> Self {
+        Self::new(name, channel, message, When::Every(interval))
+    }
+
+    pub fn cron(name: &str, channel: &str, message: &str, cron: Cron) -> Self {
+        Self::new(name, channel, message, When::Cron(cron))
+    }
+
+    fn new(name: &str, channel: &str, message: &str, when: When) -> Self {
+        Self {
+            name: name.to_string(),
+            channel: channel_name(channel),
+            message: message.to_string(),
+            when,
+            min_lines: 0,
+            jitter: Duration::ZERO,
+            enabled: true,
+        }
+    }
+
+    pub fn with_min_lines(self, min_lines: usize) -> Self {
+        Self { min_lines, ..self }
+    }
+
+    pub fn with_jitter(self, jitter: Duration) -> Self {
+        Self { jitter, ..self }
+    }
+
+    pub fn with_enabled(self, enabled: bool) -> Self {
+        Self { enabled, ..self }
+    }
+
+    /// The longest interval or jitter a schedule can have
+    pub const MAX_DELAY: Duration = Duration::from_secs(366 * 24 * 60 * 60);
+
+    /// Intervals must be between a second and [`Schedule::MAX_DELAY`],
+    /// and the jitter can't be longer than [`Schedule::MAX_DELAY`]
+    pub fn validate(&self) -> Result<(), ScheduleError> {
+        if let When::Every(interval) = self.when {
+            if interval < Duration::from_secs(1) || interval > Self::MAX_DELAY {
+                return Err(ScheduleError::InvalidInterval {
+                    name: self.name.clone(),
+                    interval,
+                });
+            }
+        }
+
+        if self.jitter > Self::MAX_DELAY {
+            return Err(ScheduleError::InvalidJitter {
+                name: self.name.clone(),
+                jitter: self.jitter,
+            });
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum ScheduleError {
+    InvalidInterval { name: String, interval: Duration },
+    InvalidJitter { name: String, jitter: Duration },
+}
+
+impl std::fmt::Display for ScheduleError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::InvalidInterval { name, interval } => {
+                write!(f, "Invalid interval for schedule {name}: {interval:?}")
+            }
+            Self::InvalidJitter { name, jitter } => {
+                write!(f, "Invalid jitter for schedule {name}: {jitter:?}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for ScheduleError {}
+
+/// A 5 field cron expression: `minute hour day-of-month month day-of-week`
+///
+/// Each field can be `*`, a number, a range `a-b`, a step `*/n` or `a-b/n`, or a list of those
+/// separated by commas. Day-of-week is `0-6` starting on Sunday (`7` is also Sunday)
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(::serde::Serialize, ::serde::Deserialize),
+    serde(try_from = "String", into = "String")
+)]
+pub struct Cron {
+    source: String,
+    minute: u64,
+    hour: u64,
+    day: u64,
+    month: u64,
+    weekday: u64,
+    // cron matches either day field when both are restricted
+    any_day: bool,
+    any_weekday: bool,
+}
+
+impl Cron {
+    pub(crate) fn matches(&self, dt: &DateTime) -> bool {
+        let has = |mask: u64, n: u32| mask & (1 << n) != 0;
+
+        let day = has(self.day, dt.day);
+        let weekday = has(self.weekday, dt.weekday);
+        let day = match (self.any_day, self.any_weekday) {
+            (false, false) => day || weekday,
+            _ => day && weekday,
+        };
+
+        day && has(self.minute, dt.minute) && has(self.hour, dt.hour) && has(self.month, dt.month)
+    }
+
+    fn field(input: &str, min: u32, max: u32) -> Result<u64, CronError> {
+        let invalid = || CronError::InvalidField {
+            field: input.to_string(),
+        };
+
+        let mut mask = 0;
+        for part in input.split(',') {
+            let (range, step) = match part.split_once('/') {
+                Some((range, step)) => (range, step.parse::<u32>().map_err(|_| invalid())?),
+                None => (part, 1),
+            };
+
+            let (start, end) = match range {
+                "*" => (min, max),
+                range => match range.split_once('-') {
+                    Some((start, end)) => (
+                        start.parse().map_err(|_| invalid())?,
+                        end.parse().map_err(|_| invalid())?,
+                    ),
+                    None if step > 1 => (range.parse().map_err(|_| invalid())?, max),
+                    None => {
+                        let n = range.parse().map_err(|_| invalid())?;
+                        (n, n)
+                    }
+                },
+            };
+
+            if step == 0 || start < min || end > max || start > end {
+                return Err(invalid());
+            }
+
+            for n in (start..=end).step_by(step as usize) {
+                mask |= 1 << n;
+            }
+        }
+
+        Ok(mask)
+    }
+}
+
+impl std::str::FromStr for Cron {
+    type Err = CronError;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        let fields = input.split_whitespace().collect::<Vec<_>>();
+        let [minute, hour, day, month, weekday] = fields[..] else {
+            return Err(CronError::FieldCount {
+                count: fields.len(),
+            });
+        };
+
+        let mut weekday_mask = Self::field(weekday, 0, 7)?;
+        if weekday_mask & (1 << 7) != 0 {
+            weekday_mask = (weekday_mask & !(1 << 7)) | 1;
+        }
+
+        Ok(Self {
+            source: fields.join(" "),
+            minute: Self::field(minute, 0, 59)?,
+            hour: Self::field(hour, 0, 23)?,
+            day: Self::field(day, 1, 31)?,
+            month: Self::field(month, 1, 12)?,
+            weekday: weekday_mask,
+            any_day: day == "*",
+            any_weekday: weekday == "*",
+        })
+    }
+}
+
+impl std::fmt::Display for Cron {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.source)
+    }
+}
+
+impl TryFrom<String> for Cron {
+    type Error = CronError;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+impl From<Cron> for String {
+    fn from(value: Cron) -> Self {
+        value.source
+    }
+}
+
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum CronError {
+    FieldCount { count: usize },
+    InvalidField { field: String },
+}
+
+impl std::fmt::Display for CronError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::FieldCount { count } => write!(f, "Expected 5 fields, got {count}"),
+            Self::InvalidField { field } => write!(f, "Invalid cron field: {field}"),
+        }
+    }
+}
+
+impl std::error::Error for CronError {}
+
+/// What a [`ScheduleStore`] keeps
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+#[non_exhaustive]
+pub struct StoredSchedules {
+    pub schedules: Vec<Schedule>,
+    /// See [`Scheduler::disable_channel`]
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub disabled_channels: Vec<String>,
+}
+
+/// Persists [`Schedule`]s (and the channels they're disabled in) so they survive a restart
+pub trait ScheduleStore: Send + Sync + 'static {
+    fn load(&self) -> std::io::Result<StoredSchedules>;
+    fn save(&self, stored: &StoredSchedules) -> std::io::Result<()>;
+}
+
+/// A [`ScheduleStore`] that keeps the schedules as JSON in a file
+#[cfg(feature = "serde")]
+pub struct JsonScheduleStore {
+    path: std::path::PathBuf,
+}
+
+#[cfg(feature = "serde")]
+impl JsonScheduleStore {
+    pub fn new(path: impl Into<std::path::PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl ScheduleStore for JsonScheduleStore {
+    fn load(&self) -> std::io::Result<StoredSchedules> {
+        match std::fs::read(&self.path) {
+            Ok(data) => serde_json::from_slice(&data).map_err(Into::into),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(<_>::default()),
+            Err(err) => Err(err),
+        }
+    }
+
+    fn save(&self, stored: &StoredSchedules) -> std::io::Result<()> {
+        let data = serde_json::to_vec_pretty(stored)?;
+        std::fs::write(&self.path, data)
+    }
+}
+
+struct Entry {
+    schedule: Schedule,
+    lines: usize,
+    next: Option<Instant>,
+    last_minute: Option<i64>,
+    due: Option<Instant>,
+}
+
+impl Entry {
+    fn new(mut schedule: Schedule) -> Self {
+        // the channel may have come from a store, or been set directly
+        schedule.channel = channel_name(&schedule.channel);
+        Self {
+            schedule,
+            lines: 0,
+            next: None,
+            last_minute: None,
+            due: None,
+        }
+    }
+}
+
+#[derive(Default)]
+struct Inner {
+    entries: Vec<Entry>,
+    disabled: HashSet<String>,
+    store: Option<Arc<dyn ScheduleStore>>,
+    task: Option<tokio::task::JoinHandle<()>>,
+}
+
+impl Inner {
+    /// What to save, so it can be saved after the lock is released
+    fn snapshot(&self) -> Option<(Arc<dyn ScheduleStore>, StoredSchedules)> {
+        let store = self.store.as_ref()?;
+        let mut disabled_channels = self.disabled.iter().cloned().collect::<Vec<_>>();
+        disabled_channels.sort();
+
+        let stored = StoredSchedules {
+            schedules: self.entries.iter().map(|e| e.schedule.clone()).collect(),
+            disabled_channels,
+        };
+        Some((Arc::clone(store), stored))
+    }
+
+    fn tick(&mut self, writer: &Writer, rng: &mut Rng) {
+        let now = Instant::now();
+        let unix = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_secs() as i64)
+            .unwrap_or_default();
+        let minute = unix / 60;
+
+        for entry in &mut self.entries {
+            let schedule = &entry.schedule;
+
+            let fire = match &schedule.when {
+                When::Every(interval) => {
+                    // schedules are validated, but don't panic if one slips through
+                    let Some(later) = now.checked_add(*interval) else {
+                        continue;
+                    };
+                    let next = *entry.next.get_or_insert(later);
+                    if next <= now {
+                        entry.next.replace(later);
+                    }
+                    next <= now
+                }
+                When::Cron(cron) => {
+                    let fire = entry.last_minute != Some(minute)
+                        && cron.matches(&DateTime::from_unix(unix));
+                    entry.last_minute.replace(minute);
+                    fire
+                }
+            };
+
+            if fire && entry.due.is_none() {
+                let jitter = rng.up_to(schedule.jitter);
+                entry.due.replace(now.checked_add(jitter).unwrap_or(now));
+            }
+
+            if !matches!(entry.due, Some(due) if due <= now) {
+                continue;
+            }
+            entry.due.take();
+
+            if !schedule.enabled
+                || self.disabled.contains(&schedule.channel)
+                || entry.lines < schedule.min_lines
+            {
+                continue;
+            }
+
+            writer.say(&schedule.channel, &schedule.message);
+            entry.lines = 0;
+        }
+    }
+}
+
+/// A [`Plugin`] that posts [`Schedule`]s
+///
+/// This is cheap to clone, so a copy can be kept around to change the schedules later
+#[derive(Clone, Default)]
+pub struct Scheduler {
+    inner: Arc<parking_lot::Mutex<Inner>>,
+    // held while saving, so saves happen in order without blocking the ticks
+    saving: Arc<parking_lot::Mutex<()>>,
+}
+
+impl Scheduler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Loads the schedules from `store`, and saves them to it whenever they change
+    ///
+    /// Stored schedules that aren't [valid](Schedule::validate) are skipped
+    pub fn with_store(store: impl ScheduleStore) -> std::io::Result<Self> {
+        let stored = store.load()?;
+        let entries = stored
+            .schedules
+            .into_iter()
+            .filter(|schedule| match schedule.validate() {
+                Ok(()) => true,
+                Err(err) => {
+                    log::warn!("skipping stored schedule: {err}");
+                    false
+                }
+            })
+            .map(Entry::new)
+            .collect();
+
+        let inner = Inner {
+            entries,
+            disabled: stored.disabled_channels.iter().map(|s| channel_name(s)).collect(),
+            store: Some(Arc::new(store)),
+            ..Inner::default()
+        };
+        Ok(Self {
+            inner: Arc::new(parking_lot::Mutex::new(inner)),
+            saving: Arc::default(),
+        })
+    }
+
+    /// Adds a schedule, replacing the one with the same name
+    pub fn add(&self, schedule: Schedule) -> Result<(), ScheduleError> {
+        schedule.validate()?;
+        self.update(|inner| {
+            inner.entries.retain(|e| e.schedule.name != schedule.name);
+            inner.entries.push(Entry::new(schedule));
+            ((), true)
+        });
+        Ok(())
+    }
+
+    pub fn remove(&self, name: &str) -> Option<Schedule> {
+        self.update(|inner| {
+            let Some(pos) = inner.entries.iter().position(|e| e.schedule.name == name) else {
+                return (None, false);
+            };
+            (Some(inner.entries.remove(pos).schedule), true)
+        })
+    }
+
+    /// Returns false if there is no schedule with this name
+    pub fn set_enabled(&self, name: &str, enabled: bool) -> bool {
+        self.update(|inner| {
+            let Some(entry) = inner.entries.iter_mut().find(|e| e.schedule.name == name) else {
+                return (false, false);
+            };
+            entry.schedule.enabled = enabled;
+            (true, true)
+        })
+    }
+
+    /// Stop posting any schedules to this channel
+    pub fn disable_channel(&self, channel: &str) {
+        self.update(|inner| ((), inner.disabled.insert(channel_name(channel))))
+    }
+
+    pub fn enable_channel(&self, channel: &str) {
+        self.update(|inner| ((), inner.disabled.remove(&channel_name(channel))))
+    }
+
+    pub fn schedules(&self) -> Vec<Schedule> {
+        let inner = self.inner.lock();
+        inner.entries.iter().map(|e| e.schedule.clone()).collect()
+    }
+
+    /// Applies `update`, and saves the schedules if it returns that they changed
+    fn update<T>(&self, update: impl FnOnce(&mut Inner) -> (T, bool)) -> T {
+        let _saving = self.saving.lock();
+        let (value, snapshot) = {
+            let mut inner = self.inner.lock();
+            let (value, changed) = update(&mut inner);
+            (value, changed.then(|| inner.snapshot()).flatten())
+        };
+
+        if let Some((store, stored)) = snapshot {
+            if let Err(err) = store.save(&stored) {
+                log::warn!("cannot save the schedules: {err}");
+            }
+        }
+        value
+    }
+
+    fn stop(&self) {
+        if let Some(task) = self.inner.lock().task.take() {
+            task.abort();
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl Plugin for Scheduler {
+    async fn on_connected<'a, 'b>(&'a mut self, _identity: &'b Identity, writer: Writer) {
+        self.stop();
+
+        let inner = Arc::clone(&self.inner);
+        let mut state = writer.state();
+        let task = tokio::spawn(async move {
+            let mut rng = Rng::new();
+            let mut interval = tokio::time::interval(Duration::from_secs(1));
+            loop {
+                tokio::select! {
+                    _ = interval.tick() => inner.lock().tick(&writer, &mut rng),
+                    // the client has quit, so there won't be an `on_disconnected`
+                    changed = state.changed() => {
+                        if changed.is_err()
+                            || state.borrow().state == ConnectionState::Stopped
+                        {
+                            break;
+                        }
+                    }
+                }
+            }
+        });
+        self.inner.lock().task.replace(task);
+    }
+
+    async fn on_disconnected<'a, 'b>(&'a mut self, error: &'b Error) -> Reconnect {
+        let _error = error;
+        self.stop();
+        Reconnect::Always
+    }
+
+    async fn on_privmsg<'a, 'b>(&'a mut self, message: &'b Privmsg<'static>, _writer: Writer) {
+        let channel = channel_name(&message.channel);
+        for entry in &mut self.inner.lock().entries {
+            if entry.schedule.channel == channel {
+                entry.lines += 1;
+            }
+        }
+    }
+}
+
+// xorshift, jitter doesn't need a good source of randomness
+struct Rng(u64);
+
+impl Rng {
+    fn new() -> Self {
+        let seed = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_nanos() as u64)
+            .unwrap_or_default();
+        Self(seed | 1)
+    }
+
+    fn up_to(&mut self, max: Duration) -> Duration {
+        if max.is_zero() {
+            return Duration::ZERO;
+        }
+
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        let max = u64::try_from(max.as_millis()).unwrap_or(u64::MAX);
+        Duration::from_millis(self.0 % max.saturating_add(1))
+    }
+}
+
+#[cfg(feature = "serde")]
+fn enabled() -> bool {
+    true
+}
+
+#[cfg(feature = "serde")]
+mod secs {
+    use std::time::Duration;
+
+    use ::serde::{Deserialize as _, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(duration: &Duration, ser: S) -> Result<S::Ok, S::Error> {
+        ser.serialize_u64(duration.as_secs())
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deser: D) -> Result<Duration, D::Error> {
+        u64::deserialize(deser).map(Duration::from_secs)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cron_matches() {
+        // 2023-04-20 14:13:20, a Thursday
+        let dt = DateTime::from_unix(1_682_000_000);
+
+        let cron = |s: &str| s.parse::<Cron>().unwrap();
+        assert!(cron("* * * * *").matches(&dt));
+        assert!(cron("13 14 * * *").matches(&dt));
+        assert!(cron("*/13 */2 20 4 *").matches(&dt));
+        assert!(cron("10-20/3 14 * * 1-5").matches(&dt));
+        assert!(cron("13 14 1 * 4").matches(&dt));
+        assert!(!cron("13 14 1 * 5").matches(&dt));
+        assert!(!cron("0,30 * * * *").matches(&dt));
+
+        assert!(matches!(
+            "* * * *".parse::<Cron>(),
+            Err(CronError::FieldCount { count: 4 })
+        ));
+        assert!("60 * * * *".parse::<Cron>().is_err());
+        assert!("*/0 * * * *".parse::<Cron>().is_err());
+    }
+
+    #[derive(Default, Clone)]
+    struct MemoryStore(Arc<parking_lot::Mutex<StoredSchedules>>);
+
+    impl ScheduleStore for MemoryStore {
+        fn load(&self) -> std::io::Result<StoredSchedules> {
+            Ok(self.0.lock().clone())
+        }
+
+        fn save(&self, stored: &StoredSchedules) -> std::io::Result<()> {
+            *self.0.lock() = stored.clone();
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn scheduler_store() {
+        let store = MemoryStore::default();
+        {
+            let mut stored = store.0.lock();
+            let mut schedule = Schedule::every("hello", "museun", "hi", Duration::from_secs(60));
+            schedule.channel = String::from("Museun");
+            stored.schedules.push(schedule.with_min_lines(1));
+            stored.schedules.push(Schedule::every("never", "museun", "hi", Duration::MAX));
+            stored.disabled_channels.push(String::from("shaken_bot"));
+        }
+
+        let scheduler = Scheduler::with_store(store.clone()).unwrap();
+        assert_eq!(scheduler.schedules().len(), 1);
+        assert_eq!(scheduler.schedules()[0].channel, "#museun");
+        assert!(scheduler.inner.lock().disabled.contains("#shaken_bot"));
+
+        scheduler.disable_channel("#Museun");
+        scheduler.enable_channel("shaken_bot");
+        assert_eq!(store.0.lock().disabled_channels, ["#museun"]);
+
+        let every = |interval| Schedule::every("test", "museun", "hi", interval);
+        assert!(matches!(
+            scheduler.add(every(Duration::ZERO)),
+            Err(ScheduleError::InvalidInterval { .. })
+        ));
+        assert!(matches!(
+            scheduler.add(every(Duration::from_secs(60)).with_jitter(Duration::MAX)),
+            Err(ScheduleError::InvalidJitter { .. })
+        ));
+        assert!(scheduler.add(every(Duration::from_secs(60))).is_ok());
+        assert_eq!(store.0.lock().schedules.len(), 2);
+    }
+
+    #[test]
+    fn rng_up_to() {
+        let mut rng = Rng::new();
+        assert_eq!(rng.up_to(Duration::ZERO), Duration::ZERO);
+        assert!(rng.up_to(Duration::from_secs(1)) <= Duration::from_secs(1));
+        let _ = rng.up_to(Duration::MAX);
+    }
+}
@@ -0,0 +1,39 @@
+use std::time::Duration;
+
+/// Where the connection is in its lifecycle, see [`Writer::state`](crate::Writer::state)
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ConnectionState {
+    /// Opening the socket and registering
+    #[default]
+    Connecting,
+    /// Twitch has accepted our registration, but we aren't in any channels
+    Registered,
+    /// We're in at least one channel
+    Joined,
+    /// The connection was lost, another attempt will be made after `backoff`
+    WaitingToReconnect,
+    /// The connection has been closed and won't be reopened
+    Stopped,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct ConnectionStatus {
+    pub state: ConnectionState,
+    /// How long we are waiting before reconnecting
+    pub backoff: Option<Duration>,
+    /// The error from the last disconnect
+    pub last_error: Option<String>,
+    /// How many channels we are in
+    pub channels: usize,
+}
+
+impl ConnectionStatus {
+    pub const fn is_connected(&self) -> bool {
+        matches!(
+            self.state,
+            ConnectionState::Registered | ConnectionState::Joined
+        )
+    }
+}
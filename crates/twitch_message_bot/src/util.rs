@@ -15,3 +15,86 @@ where
         right = right => Either::Right(right),
     }
 }
+
+/// The channel with a leading `#`, e.g. `#museun` for `Museun`
+pub fn channel_name(channel: &str) -> String {
+    format!("#{}", channel_login(channel))
+}
+
+/// The channel without a leading `#`, e.g. `museun` for `#Museun`
+pub fn channel_login(channel: &str) -> String {
+    channel.trim().trim_start_matches('#').to_ascii_lowercase()
+}
+
+/// The lines of a chat message, each is sent as its own message. Empty lines are skipped
+pub fn lines(data: &str) -> impl Iterator<Item = &str> {
+    data.split('\n').map(str::trim).filter(|line| !line.is_empty())
+}
+
+/// A UTC date and time, without pulling in a date crate
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct DateTime {
+    pub year: i64,
+    pub month: u32,
+    pub day: u32,
+    pub hour: u32,
+    pub minute: u32,
+    pub second: u32,
+    /// `0` is Sunday
+    pub weekday: u32,
+}
+
+impl DateTime {
+    pub fn from_unix(secs: i64) -> Self {
+        let (days, rem) = (secs.div_euclid(86400), secs.rem_euclid(86400));
+
+        // http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+        let z = days + 719_468;
+        let era = z.div_euclid(146_097);
+        let doe = z.rem_euclid(146_097);
+        let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146_096) / 365;
+        let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+        let mp = (5 * doy + 2) / 153;
+        let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+        let month = (if mp < 10 { mp + 3 } else { mp - 9 }) as u32;
+        let year = yoe + era * 400 + i64::from(month <= 2);
+
+        Self {
+            year,
+            month,
+            day,
+            hour: (rem / 3600) as u32,
+            minute: (rem % 3600 / 60) as u32,
+            second: (rem % 60) as u32,
+            // 1970-01-01 was a Thursday
+            weekday: (days + 4).rem_euclid(7) as u32,
+        }
+    }
+
+    pub fn date(&self) -> String {
+        format!("{:04}-{:02}-{:02}", self.year, self.month, self.day)
+    }
+
+    pub fn time(&self) -> String {
+        format!("{:02}:{:02}:{:02}", self.hour, self.minute, self.second)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn date_time_from_unix() {
+        let dt = DateTime::from_unix(0);
+        assert_eq!((dt.year, dt.month, dt.day), (1970, 1, 1));
+
+        let dt = DateTime::from_unix(1_682_000_000);
+        assert_eq!(dt.date(), "2023-04-20");
+        assert_eq!(dt.time(), "14:13:20");
+        assert_eq!(dt.weekday, 4);
+
+        let dt = DateTime::from_unix(951_782_400);
+        assert_eq!(dt.date(), "2000-02-29");
+    }
+}
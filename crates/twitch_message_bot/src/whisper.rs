@@ -0,0 +1,316 @@
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    time::{Duration, Instant},
+};
+
+use tokio::sync::mpsc::{UnboundedReceiver, UnboundedSender};
+
+use crate::Config;
+
+// https://dev.twitch.tv/docs/api/reference/#send-whisper
+const PER_SECOND: usize = 3;
+const PER_MINUTE: usize = 100;
+const RECIPIENTS_PER_DAY: usize = 40;
+
+enum Request {
+    UserId(Box<str>),
+    Whisper(Box<str>, Box<str>),
+}
+
+/// Sends whispers through Helix
+///
+/// This is created once per client so the rate limits (and the recipient cache) survive reconnects
+pub(crate) struct Whispers {
+    sender: UnboundedSender<Request>,
+}
+
+impl Whispers {
+    pub(crate) fn spawn(config: &Config) -> Self {
+        let (sender, recv) = tokio::sync::mpsc::unbounded_channel();
+
+        let helix = Helix {
+            client: reqwest::Client::new(),
+            base: config.helix_url.trim_end_matches('/').to_string(),
+            client_id: config.client_id.clone(),
+            token: config
+                .token
+                .strip_prefix("oauth:")
+                .unwrap_or(&config.token)
+                .to_string(),
+            user_id: None,
+            ids: HashMap::new(),
+        };
+
+        tokio::spawn(Self::run(helix, recv));
+        Self { sender }
+    }
+
+    /// Sets the user id whispers are sent from, this is updated on every (re)connect
+    pub(crate) fn set_user_id(&self, user_id: &str) {
+        let _ = self.sender.send(Request::UserId(user_id.into()));
+    }
+
+    pub(crate) fn send(&self, target: &str, data: &str) {
+        let _ = self.sender.send(Request::Whisper(target.into(), data.into()));
+    }
+
+    async fn run(mut helix: Helix, mut recv: UnboundedReceiver<Request>) {
+        let mut limit = RateLimit::new(Instant::now());
+
+        while let Some(req) = recv.recv().await {
+            let (target, data) = match req {
+                Request::UserId(user_id) => {
+                    helix.user_id.replace(user_id.into());
+                    continue;
+                }
+                Request::Whisper(target, data) => (target, data),
+            };
+
+            let target = target.to_ascii_lowercase();
+            if !limit.allow_recipient(&target, Instant::now()) {
+                log::warn!("whisper recipient limit reached, dropping whisper to: {target}");
+                continue;
+            }
+
+            while let Some(delay) = limit.delay(Instant::now()) {
+                log::trace!("whisper rate limited, waiting: {delay:.2?}");
+                tokio::time::sleep(delay).await;
+            }
+            limit.record(Instant::now());
+
+            match helix.send_whisper(&target, &data).await {
+                Ok(()) => limit.add_recipient(&target),
+                Err(err) => log::warn!("cannot send whisper to {target}: {err}"),
+            }
+        }
+    }
+}
+
+struct Helix {
+    client: reqwest::Client,
+    base: String,
+    client_id: Option<String>,
+    token: String,
+    user_id: Option<String>,
+    ids: HashMap<String, String>,
+}
+
+impl Helix {
+    async fn send_whisper(&mut self, target: &str, data: &str) -> Result<(), HelixError> {
+        let to_user_id = self.lookup_user_id(target).await?;
+        let from_user_id = self.user_id.as_deref().ok_or(HelixError::NotConnected)?;
+
+        let resp = self
+            .request(reqwest::Method::POST, "whispers")?
+            .query(&[("from_user_id", from_user_id), ("to_user_id", &*to_user_id)])
+            .json(&serde_json::json!({ "message": data }))
+            .send()
+            .await
+            .map_err(HelixError::Request)?;
+
+        match resp.status() {
+            status if status.is_success() => Ok(()),
+            status => Err(HelixError::Status(status)),
+        }
+    }
+
+    async fn lookup_user_id(&mut self, login: &str) -> Result<String, HelixError> {
+        if let Some(id) = self.ids.get(login) {
+            return Ok(id.clone());
+        }
+
+        let resp = self
+            .request(reqwest::Method::GET, "users")?
+            .query(&[("login", login)])
+            .send()
+            .await
+            .map_err(HelixError::Request)?;
+
+        if !resp.status().is_success() {
+            return Err(HelixError::Status(resp.status()));
+        }
+
+        let body: serde_json::Value = resp.json().await.map_err(HelixError::Request)?;
+        let id = body["data"][0]["id"]
+            .as_str()
+            .ok_or_else(|| HelixError::UnknownUser {
+                login: login.to_string(),
+            })?
+            .to_string();
+
+        self.ids.insert(login.to_string(), id.clone());
+        Ok(id)
+    }
+
+    fn request(
+        &self,
+        method: reqwest::Method,
+        endpoint: &str,
+    ) -> Result<reqwest::RequestBuilder, HelixError> {
+        let client_id = self.client_id.as_deref().ok_or(HelixError::MissingClientId)?;
+
+        Ok(self
+            .client
+            .request(method, format!("{}/{endpoint}", self.base))
+            .header("Client-Id", client_id)
+            .bearer_auth(&self.token))
+    }
+}
+
+#[derive(Debug)]
+enum HelixError {
+    MissingClientId,
+    NotConnected,
+    UnknownUser { login: String },
+    Status(reqwest::StatusCode),
+    Request(reqwest::Error),
+}
+
+impl std::fmt::Display for HelixError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::MissingClientId => f.write_str("no client id was configured"),
+            Self::NotConnected => f.write_str("our user id isn't known yet"),
+            Self::UnknownUser { login } => write!(f, "unknown user: {login}"),
+            Self::Status(status) => write!(f, "unexpected status: {status}"),
+            Self::Request(error) => write!(f, "request failed: {error}"),
+        }
+    }
+}
+
+struct RateLimit {
+    windows: [Window; 2],
+    recipients: HashSet<String>,
+    day: Instant,
+}
+
+impl RateLimit {
+    const DAY: Duration = Duration::from_secs(60 * 60 * 24);
+
+    fn new(now: Instant) -> Self {
+        Self {
+            windows: [
+                Window::new(PER_SECOND, Duration::from_secs(1)),
+                Window::new(PER_MINUTE, Duration::from_secs(60)),
+            ],
+            recipients: HashSet::new(),
+            day: now,
+        }
+    }
+
+    /// Whether `target` can be whispered today. New recipients only count once
+    /// [`RateLimit::add_recipient`] is called, after a whisper to them was sent
+    fn allow_recipient(&mut self, target: &str, now: Instant) -> bool {
+        if now.duration_since(self.day) >= Self::DAY {
+            self.recipients.clear();
+            self.day = now;
+        }
+
+        self.recipients.contains(target) || self.recipients.len() < RECIPIENTS_PER_DAY
+    }
+
+    fn add_recipient(&mut self, target: &str) {
+        self.recipients.insert(target.to_string());
+    }
+
+    fn delay(&mut self, now: Instant) -> Option<Duration> {
+        self.windows.iter_mut().filter_map(|w| w.delay(now)).max()
+    }
+
+    fn record(&mut self, now: Instant) {
+        self.windows.iter_mut().for_each(|w| w.sent.push_back(now));
+    }
+}
+
+struct Window {
+    limit: usize,
+    period: Duration,
+    sent: VecDeque<Instant>,
+}
+
+impl Window {
+    fn new(limit: usize, period: Duration) -> Self {
+        Self {
+            limit,
+            period,
+            sent: VecDeque::with_capacity(limit),
+        }
+    }
+
+    fn delay(&mut self, now: Instant) -> Option<Duration> {
+        while matches!(self.sent.front(), Some(&then) if now.duration_since(then) >= self.period) {
+            self.sent.pop_front();
+        }
+
+        (self.sent.len() >= self.limit)
+            .then(|| self.sent.front().map(|&then| self.period - now.duration_since(then)))
+            .flatten()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn whisper_per_second() {
+        let start = Instant::now();
+        let mut limit = RateLimit::new(start);
+
+        for _ in 0..PER_SECOND {
+            assert_eq!(limit.delay(start), None);
+            limit.record(start);
+        }
+
+        assert_eq!(limit.delay(start), Some(Duration::from_secs(1)));
+        let later = start + Duration::from_millis(400);
+        assert_eq!(limit.delay(later), Some(Duration::from_millis(600)));
+        assert_eq!(limit.delay(start + Duration::from_secs(1)), None);
+    }
+
+    #[test]
+    fn whisper_per_minute() {
+        let start = Instant::now();
+        let mut limit = RateLimit::new(start);
+
+        // two a second stays under the per-second limit
+        let step = Duration::from_millis(500);
+        for i in 0..PER_MINUTE as u32 {
+            let now = start + step * i;
+            assert_eq!(limit.delay(now), None);
+            limit.record(now);
+        }
+
+        let now = start + step * PER_MINUTE as u32;
+        assert_eq!(limit.delay(now), Some(Duration::from_secs(10)));
+        assert_eq!(limit.delay(start + Duration::from_secs(60)), None);
+    }
+
+    #[test]
+    fn whisper_recipients_per_day() {
+        let start = Instant::now();
+        let mut limit = RateLimit::new(start);
+
+        for i in 0..RECIPIENTS_PER_DAY - 1 {
+            let target = format!("user{i}");
+            assert!(limit.allow_recipient(&target, start));
+            limit.add_recipient(&target);
+        }
+
+        // a whisper that failed doesn't use up a slot
+        assert!(limit.allow_recipient("failed", start));
+        assert!(limit.allow_recipient("last", start));
+        limit.add_recipient("last");
+
+        assert!(!limit.allow_recipient("failed", start));
+        assert!(!limit.allow_recipient("another", start));
+        // known recipients can still be whispered
+        assert!(limit.allow_recipient("user0", start));
+        assert!(limit.allow_recipient("last", start));
+
+        let tomorrow = start + RateLimit::DAY;
+        assert!(limit.allow_recipient("another", tomorrow));
+        limit.add_recipient("another");
+        assert_eq!(limit.recipients.len(), 1);
+    }
+}
@@ -0,0 +1,196 @@
+use std::{collections::HashMap, sync::Arc};
+
+use tokio::sync::{
+    mpsc::{error::TrySendError, Sender},
+    Mutex,
+};
+use twitch_message::messages::{Message, Privmsg, Whisper};
+
+use crate::{ChannelEvent, Handler, Identity, Writer};
+
+/// How incoming messages are handed to the [`Handler`](crate::Handler)
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum DispatchMode {
+    /// Every callback is awaited by the connection loop before it reads the next message
+    #[default]
+    Inline,
+    /// Each channel gets its own queue, messages (including joins and parts) are delivered in
+    /// order per channel. The connection loop never waits on the handler in this mode, so a slow
+    /// callback can't hold up reading or answering pings.
+    ///
+    /// This only changes how messages are queued, not how many callbacks run at once. There is
+    /// a single handler behind a single lock, so callbacks for different channels still run one
+    /// after the other, and a slow callback in one channel delays every other channel.
+    /// [`Handler::on_connected`] is queued like any other message, it may still be running when
+    /// the first channel messages are delivered.
+    ///
+    /// `capacity` is how many messages can be waiting for a channel before new ones are dropped.
+    /// Every dropped message is logged as a warning
+    PerChannel { capacity: usize },
+}
+
+pub(crate) enum Delivery {
+    Connected(Identity),
+    Join(Box<str>),
+    Part(Box<str>),
+    Privmsg(Privmsg<'static>),
+    OwnPrivmsg(Privmsg<'static>),
+    Whisper(Whisper<'static>),
+    ChannelEvent(ChannelEvent),
+    Message(Message<'static>),
+}
+
+impl Delivery {
+    const fn kind(&self) -> &'static str {
+        match self {
+            Self::Connected(..) => "connected",
+            Self::Join(..) => "join",
+            Self::Part(..) => "part",
+            Self::Privmsg(..) => "privmsg",
+            Self::OwnPrivmsg(..) => "own privmsg",
+            Self::Whisper(..) => "whisper",
+            Self::ChannelEvent(..) => "channel event",
+            Self::Message(..) => "message",
+        }
+    }
+
+    async fn deliver(self, handler: &mut impl Handler, writer: Writer) {
+        match self {
+            Self::Connected(identity) => handler.on_connected(identity, writer).await,
+            Self::Join(channel) => handler.on_join(&channel).await,
+            Self::Part(channel) => handler.on_part(&channel).await,
+            Self::Privmsg(msg) => handler.on_privmsg(msg, writer).await,
+            Self::OwnPrivmsg(msg) => handler.on_own_privmsg(msg, writer).await,
+            Self::Whisper(msg) => handler.on_whisper(msg, writer).await,
+            Self::ChannelEvent(event) => handler.on_channel_event(event, writer).await,
+            Self::Message(msg) => handler.on_twitch_message(msg, writer).await,
+        }
+    }
+}
+
+pub(crate) struct Workers<H> {
+    handler: Arc<Mutex<H>>,
+    writer: Writer,
+    mode: DispatchMode,
+    channels: HashMap<Box<str>, Sender<Delivery>>,
+}
+
+impl<H: Handler> Workers<H> {
+    pub(crate) fn new(handler: Arc<Mutex<H>>, writer: Writer, mode: DispatchMode) -> Self {
+        Self {
+            handler,
+            writer,
+            mode,
+            channels: HashMap::new(),
+        }
+    }
+
+    pub(crate) async fn deliver(&mut self, channel: &str, delivery: Delivery) {
+        let capacity = match self.mode {
+            DispatchMode::Inline => {
+                let mut handler = self.handler.lock().await;
+                return delivery.deliver(&mut *handler, self.writer.clone()).await;
+            }
+            DispatchMode::PerChannel { capacity } => capacity.max(1),
+        };
+
+        let sender = self.channels.entry(channel.into()).or_insert_with(|| {
+            let (tx, mut rx) = tokio::sync::mpsc::channel(capacity);
+            let handler = Arc::clone(&self.handler);
+            let writer = self.writer.clone();
+
+            tokio::spawn(async move {
+                while let Some(delivery) = rx.recv().await {
+                    let mut handler = handler.lock().await;
+                    delivery.deliver(&mut *handler, writer.clone()).await;
+                }
+            });
+            tx
+        });
+
+        match sender.try_send(delivery) {
+            Ok(..) => {}
+            Err(TrySendError::Full(delivery)) => {
+                log::warn!(
+                    "handler queue for '{channel}' is full ({capacity} waiting), dropping: {}",
+                    delivery.kind()
+                )
+            }
+            Err(TrySendError::Closed(..)) => {
+                log::warn!("handler task for '{channel}' has stopped");
+                self.channels.remove(channel);
+            }
+        }
+    }
+
+    /// Stops the task for this channel once it has delivered everything it has queued
+    pub(crate) fn remove(&mut self, channel: &str) {
+        self.channels.remove(channel);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::sync::{mpsc::UnboundedSender, Notify, Semaphore};
+    use twitch_message::IntoStatic as _;
+
+    use super::*;
+
+    /// Blocks in `on_privmsg` until the gate is opened
+    struct Gated {
+        started: Arc<Notify>,
+        gate: Arc<Semaphore>,
+        seen: UnboundedSender<String>,
+    }
+
+    #[async_trait::async_trait]
+    impl Handler for Gated {
+        async fn on_connected<'a>(&'a mut self, _identity: Identity, _writer: Writer) {}
+
+        async fn on_privmsg<'a>(&'a mut self, message: Privmsg<'static>, _writer: Writer) {
+            self.started.notify_one();
+            self.gate.acquire().await.unwrap().forget();
+            let _ = self.seen.send(message.data.to_string());
+        }
+    }
+
+    fn privmsg(data: &str) -> Delivery {
+        let line = format!(":museun!museun@museun.tmi.twitch.tv PRIVMSG #museun :{data}\r\n");
+        let msg = twitch_message::parse(&line).unwrap().message.into_static();
+        Delivery::Privmsg(msg.as_typed_message::<Privmsg>().unwrap().clone())
+    }
+
+    #[tokio::test]
+    async fn per_channel_overflow() {
+        let (seen, mut rx) = tokio::sync::mpsc::unbounded_channel();
+        let started = Arc::new(Notify::new());
+        let gate = Arc::new(Semaphore::new(0));
+        let handler = Gated {
+            started: Arc::clone(&started),
+            gate: Arc::clone(&gate),
+            seen,
+        };
+
+        let mode = DispatchMode::PerChannel { capacity: 1 };
+        let (writer, _recv) = Writer::new();
+        let mut workers = Workers::new(Arc::new(Mutex::new(handler)), writer, mode);
+
+        // the first is being handled, the second waits in the queue and the rest are dropped
+        workers.deliver("#museun", privmsg("1")).await;
+        started.notified().await;
+        workers.deliver("#museun", privmsg("2")).await;
+        workers.deliver("#museun", privmsg("3")).await;
+        workers.deliver("#museun", privmsg("4")).await;
+
+        gate.add_permits(Semaphore::MAX_PERMITS);
+        workers.remove("#museun");
+        drop(workers);
+
+        let mut delivered = vec![];
+        while let Some(data) = rx.recv().await {
+            delivered.push(data);
+        }
+        assert_eq!(delivered, ["1", "2"]);
+    }
+}
@@ -1,55 +1,292 @@
-use tokio::sync::mpsc::{UnboundedReceiver, UnboundedSender};
+use std::{
+    future::Future,
+    sync::Arc,
+    time::{Duration, SystemTime},
+};
+
+use tokio::sync::{
+    mpsc::{UnboundedReceiver, UnboundedSender},
+    watch,
+};
 use twitch_message::messages::{types::MsgId, Privmsg};
 
+use crate::{
+    delayed::Delayed, join::PendingJoins, util::channel_name, Config, ConnectionStatus,
+    DelayedError, DelayedHandle, JoinError,
+};
+
+/// What [`Writer::reply`] does when the message has no `msg-id` to reply to
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ReplyPolicy {
+    /// Send a normal message to the channel instead
+    #[default]
+    Fallback,
+    /// Return [`ReplyError::MissingMsgId`]
+    Strict,
+}
+
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum ReplyError {
+    MissingMsgId,
+}
+
+impl std::fmt::Display for ReplyError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::MissingMsgId => f.write_str("Cannot reply to a message without a msg-id"),
+        }
+    }
+}
+
+impl std::error::Error for ReplyError {}
+
 #[derive(Clone)]
 pub struct Writer {
-    sender: UnboundedSender<WriteKind>,
+    sender: UnboundedSender<QueuedWrite>,
+    reply_policy: ReplyPolicy,
+    status: Arc<watch::Sender<ConnectionStatus>>,
+    joins: Arc<PendingJoins>,
+    delayed: Arc<Delayed>,
 }
 
 impl Writer {
     #[doc(hidden)]
-    pub fn new() -> (Self, UnboundedReceiver<WriteKind>) {
+    pub fn new() -> (Self, UnboundedReceiver<QueuedWrite>) {
         let (sender, rx) = tokio::sync::mpsc::unbounded_channel();
-        (Self { sender }, rx)
+        let (status, _) = watch::channel(ConnectionStatus::default());
+        let this = Self {
+            sender,
+            reply_policy: ReplyPolicy::default(),
+            status: Arc::new(status),
+            joins: Arc::default(),
+            delayed: Arc::default(),
+        };
+        (this, rx)
+    }
+
+    pub(crate) fn for_config(config: &Config) -> (Self, UnboundedReceiver<QueuedWrite>) {
+        let (mut this, rx) = Self::new();
+        if let Some(store) = &config.delayed_store {
+            this.delayed = Delayed::restore(Arc::clone(store), &this.sender);
+        }
+        (this.with_reply_policy(config.reply_policy), rx)
+    }
+
+    /// A copy of this writer that uses a different [`ReplyPolicy`]
+    pub fn with_reply_policy(self, reply_policy: ReplyPolicy) -> Self {
+        Self {
+            reply_policy,
+            ..self
+        }
+    }
+
+    /// Watch the state of the connection
+    ///
+    /// The receiver sees every change made after it was created, and the current status
+    pub fn state(&self) -> watch::Receiver<ConnectionStatus> {
+        self.status.subscribe()
+    }
+
+    pub(crate) fn update_status(&self, update: impl FnOnce(&mut ConnectionStatus)) {
+        self.status.send_modify(update)
+    }
+
+    pub(crate) fn pending_joins(&self) -> &PendingJoins {
+        &self.joins
+    }
+
+    pub(crate) fn delayed(&self) -> &Delayed {
+        &self.delayed
+    }
+
+    fn send(&self, kind: WriteKind) {
+        let _ = self.sender.send(kind.into());
     }
 }
 
 impl Writer {
     pub fn join_channel(&self, channel: impl ToString) {
-        let _ = self.sender.send(WriteKind::Join {
+        self.send(WriteKind::Join {
             channel: channel.to_string().into(),
         });
     }
 
+    /// Join a channel, the returned future resolves once Twitch has confirmed the join
+    ///
+    /// Dropping the future doesn't cancel the join
+    pub fn join_channel_confirmed(
+        &self,
+        channel: impl ToString,
+        timeout: Duration,
+    ) -> impl Future<Output = Result<(), JoinError>> + Send + 'static {
+        let channel = channel.to_string();
+        let rx = self.joins.wait(&channel);
+        self.join_channel(&channel);
+
+        let joins = Arc::clone(&self.joins);
+        async move {
+            match tokio::time::timeout(timeout, rx).await {
+                Ok(Ok(result)) => result,
+                Ok(Err(..)) => Err(JoinError::Disconnected),
+                Err(..) => {
+                    joins.prune(&channel);
+                    Err(JoinError::Timeout)
+                }
+            }
+        }
+    }
+
     pub fn part_channel(&self, channel: impl ToString) {
-        let _ = self.sender.send(WriteKind::Part {
+        self.send(WriteKind::Part {
             channel: channel.to_string().into(),
         });
     }
 
     pub fn send_raw(&self, raw: impl ToString) {
-        let _ = self.sender.send(WriteKind::Raw {
+        self.send(WriteKind::Raw {
             raw: raw.to_string().into(),
         });
     }
 
     pub fn privmsg(&self, message: &Privmsg<'_>, data: impl ToString) {
-        let _ = self.sender.send(WriteKind::Privmsg {
+        self.send(WriteKind::Privmsg {
             target: message.channel.clone().into(),
             data: data.to_string().into(),
         });
     }
 
-    pub fn reply(&self, message: &Privmsg<'_>, data: impl ToString) {
-        let _ = self.sender.send(WriteKind::Reply {
-            id: message.msg_id().expect("msg-id attached").to_owned(),
-            target: message.channel.clone().into(),
+    /// Send a message to a channel
+    pub fn say(&self, channel: &str, data: impl ToString) {
+        self.send(WriteKind::Privmsg {
+            target: channel_name(channel).into(),
+            data: data.to_string().into(),
+        });
+    }
+
+    /// Send a `/me` message to a channel
+    pub fn action(&self, channel: &str, data: impl ToString) {
+        // an action can't span multiple messages
+        let data = data.to_string().replace('\n', " ");
+        self.send(WriteKind::Privmsg {
+            target: channel_name(channel).into(),
+            data: format!("\x01ACTION {}\x01", data.trim()).into(),
+        });
+    }
+
+    /// Send a message to a channel after `delay`
+    ///
+    /// Returns [`DelayedError::OutOfRange`] if `delay` is too long to represent as a time
+    pub fn send_after(
+        &self,
+        channel: &str,
+        data: impl ToString,
+        delay: Duration,
+    ) -> Result<DelayedHandle, DelayedError> {
+        let at = SystemTime::now()
+            .checked_add(delay)
+            .ok_or(DelayedError::OutOfRange { delay })?;
+        Ok(self.send_at(channel, data, at))
+    }
+
+    /// Send a message to a channel at `at`
+    ///
+    /// If the connection is down at that time, the message is sent once it is back up.
+    /// With a [`DelayedStore`](crate::DelayedStore) configured, the message is also
+    /// sent if the bot was restarted in the meantime
+    pub fn send_at(&self, channel: &str, data: impl ToString, at: SystemTime) -> DelayedHandle {
+        self.delayed
+            .send_at(channel_name(channel).into(), data.to_string(), at, &self.sender)
+    }
+
+    /// A [`ChannelWriter`] that sends to `channel`
+    pub fn channel(&self, channel: &str) -> ChannelWriter {
+        ChannelWriter {
+            channel: channel_name(channel).into(),
+            writer: self.clone(),
+        }
+    }
+
+    pub fn reply(&self, message: &Privmsg<'_>, data: impl ToString) -> Result<(), ReplyError> {
+        let kind = match (message.msg_id(), self.reply_policy) {
+            (Some(id), ..) => WriteKind::Reply {
+                id: id.to_owned(),
+                target: message.channel.clone().into(),
+                data: data.to_string().into(),
+            },
+            (None, ReplyPolicy::Fallback) => WriteKind::Privmsg {
+                target: message.channel.clone().into(),
+                data: data.to_string().into(),
+            },
+            (None, ReplyPolicy::Strict) => return Err(ReplyError::MissingMsgId),
+        };
+
+        self.send(kind);
+        Ok(())
+    }
+
+    pub fn whisper(&self, user: impl ToString, data: impl ToString) {
+        self.send(WriteKind::Whisper {
+            target: user.to_string().into(),
             data: data.to_string().into(),
         });
     }
 
     pub fn quit(&self) {
-        let _ = self.sender.send(WriteKind::Quit);
+        self.send(WriteKind::Quit);
+    }
+}
+
+/// A [`Writer`] bound to a single channel, see [`Writer::channel`]
+#[derive(Clone)]
+pub struct ChannelWriter {
+    channel: Arc<str>,
+    writer: Writer,
+}
+
+impl ChannelWriter {
+    /// The channel this sends to, including the leading `#`
+    pub fn name(&self) -> &str {
+        &self.channel
+    }
+
+    pub fn say(&self, data: impl ToString) {
+        self.writer.say(&self.channel, data)
+    }
+
+    pub fn action(&self, data: impl ToString) {
+        self.writer.action(&self.channel, data)
+    }
+
+    pub fn join(&self) {
+        self.writer.join_channel(&self.channel)
+    }
+
+    pub fn part(&self) {
+        self.writer.part_channel(&self.channel)
+    }
+
+    pub fn writer(&self) -> &Writer {
+        &self.writer
+    }
+}
+
+/// A [`WriteKind`] waiting for the client to write it
+#[doc(hidden)]
+#[derive(Debug)]
+pub struct QueuedWrite {
+    pub kind: WriteKind,
+    /// Messages from [`Writer::send_at`] stay in their store until this is written
+    pub(crate) delayed: Option<u64>,
+}
+
+impl From<WriteKind> for QueuedWrite {
+    fn from(kind: WriteKind) -> Self {
+        Self {
+            kind,
+            delayed: None,
+        }
     }
 }
 
@@ -74,9 +311,41 @@ pub enum WriteKind {
         target: Box<str>,
         data: Box<str>,
     },
+    Whisper {
+        target: Box<str>,
+        data: Box<str>,
+    },
     Quit,
 }
 
+impl WriteKind {
+    pub fn channel(&self) -> Option<&str> {
+        match self {
+            Self::Join { channel } | Self::Part { channel } => Some(channel),
+            Self::Privmsg { target, .. } | Self::Reply { target, .. } => Some(target),
+            _ => None,
+        }
+    }
+
+    pub fn data(&self) -> Option<&str> {
+        match self {
+            Self::Privmsg { data, .. } | Self::Reply { data, .. } | Self::Whisper { data, .. } => {
+                Some(data)
+            }
+            _ => None,
+        }
+    }
+
+    pub fn data_mut(&mut self) -> Option<&mut Box<str>> {
+        match self {
+            Self::Privmsg { data, .. } | Self::Reply { data, .. } | Self::Whisper { data, .. } => {
+                Some(data)
+            }
+            _ => None,
+        }
+    }
+}
+
 impl std::fmt::Display for WriteKind {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         use twitch_message::encode::*;
@@ -86,6 +355,10 @@ impl std::fmt::Display for WriteKind {
             Self::Raw { raw: msg } => raw(msg).format(f),
             Self::Privmsg { target, data } => privmsg(target, data).format(f),
             Self::Reply { id, target, data } => reply(id, target, data).format(f),
+            // whispers are sent through helix, this is the old irc form
+            Self::Whisper { target, data } => {
+                privmsg("#jtv", &format!("/w {target} {data}")).format(f)
+            }
             Self::Quit => f.write_str("QUIT\r\n"),
         }
     }
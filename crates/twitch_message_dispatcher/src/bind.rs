@@ -1,13 +1,20 @@
 use std::{future::Future, pin::Pin, sync::Arc};
 
 use twitch_message::messages::Privmsg;
-use twitch_message_bot::Writer;
+use twitch_message_bot::{ChannelEvent, Writer};
 
-use crate::{command::Access, Arguments, Command, Context, Match, Outcome};
+use crate::{
+    command::Access, cooldown::Cooldowns, prefix::Prefixes, Arguments, Command, Context,
+    EventContext, Match, Outcome,
+};
 
 pub(crate) type BoxFuture<'a, T = ()> = Pin<Box<dyn Future<Output = T> + Send + Sync + 'a>>;
-pub(crate) type Callable =
-    dyn Fn(Arc<Privmsg<'static>>, Writer) -> BoxFuture<'static> + Send + Sync + 'static;
+pub(crate) type Callable = dyn Fn(Arc<Privmsg<'static>>, Writer, Prefixes) -> BoxFuture<'static>
+    + Send
+    + Sync
+    + 'static;
+pub(crate) type EventCallable =
+    dyn Fn(Arc<ChannelEvent>, Writer) -> BoxFuture<'static> + Send + Sync + 'static;
 
 #[derive(Debug, Copy, Clone)]
 #[non_exhaustive]
@@ -35,6 +42,7 @@ where
 {
     this: Arc<tokio::sync::Mutex<T>>,
     handlers: Vec<Arc<Callable>>,
+    events: Vec<Arc<EventCallable>>,
 }
 
 impl<T> Bind<T>
@@ -45,6 +53,7 @@ where
         Self {
             this: Arc::new(tokio::sync::Mutex::new(this)),
             handlers: Vec::new(),
+            events: Vec::new(),
         }
     }
 
@@ -56,6 +65,7 @@ where
     {
         let this = Arc::clone(&self.this);
         let cmd = Arc::new(cmd);
+        let cooldowns = Arc::new(parking_lot::Mutex::new(Cooldowns::default()));
 
         let opts = opts;
 
@@ -66,24 +76,42 @@ where
             crate::CommandFile::add(&cmd).expect("command file is initialized");
         }
 
-        let this = move |msg: Arc<Privmsg<'static>>, writer: Writer| -> BoxFuture<'static> {
+        let this = move |msg: Arc<Privmsg<'static>>,
+                         writer: Writer,
+                         prefixes: Prefixes|
+              -> BoxFuture<'static> {
             let this = Arc::clone(&this);
             let cmd = Arc::clone(&cmd);
+            let cooldowns = Arc::clone(&cooldowns);
 
             let fut = async move {
-                let arguments = {
+                let check = |cmd: &Command| {
+                    Self::check_cmd_access(cmd, &msg, &writer, &prefixes, opts)
+                        .map(|args| (args, cmd.cooldown.clone()))
+                };
+
+                let (arguments, cooldown) = {
                     let Some(args) = (if opts.use_command_file {
                         match crate::CommandFile::get_ref(&cmd.id) {
-                            Ok(cmd) => Self::check_cmd_access(&cmd, &msg, &writer, opts),
-                            _ => Self::check_cmd_access(&cmd, &msg, &writer, opts),
+                            Ok(cmd) => check(&cmd),
+                            _ => check(&cmd),
                         }
                     } else {
-                        Self::check_cmd_access(&cmd, &msg, &writer, opts)
+                        check(&cmd)
                     }) else { return };
 
                     args
                 };
 
+                let used = cooldowns.lock().try_use(&cooldown, &msg);
+                if let Err(remaining) = used {
+                    if cooldown.reply {
+                        let remaining = crate::duration::format(remaining);
+                        let _ = writer.reply(&msg, format!("try again in {remaining}"));
+                    }
+                    return;
+                }
+
                 let outcome = {
                     let context = Context {
                         msg: Arc::clone(&msg),
@@ -95,7 +123,7 @@ where
 
                 if opts.report_command_error {
                     if let Some(error) = outcome.as_error() {
-                        writer.reply(&msg, error);
+                        let _ = writer.reply(&msg, error);
                     }
                 }
             };
@@ -117,7 +145,10 @@ where
         let this = Arc::clone(&self.this);
         let opts = opts;
 
-        let this = move |msg: Arc<Privmsg<'static>>, writer: Writer| -> BoxFuture<'static> {
+        let this = move |msg: Arc<Privmsg<'static>>,
+                         writer: Writer,
+                         _prefixes: Prefixes|
+              -> BoxFuture<'static> {
             let this = Arc::clone(&this);
 
             Box::pin(async move {
@@ -125,7 +156,7 @@ where
                 let this = &mut *guard;
                 if let Some(err) = handler(this, &msg, &writer).await.as_error() {
                     if opts.report_command_error {
-                        writer.reply(&msg, err);
+                        let _ = writer.reply(&msg, err);
                     }
                 }
             })
@@ -135,18 +166,58 @@ where
         self
     }
 
+    /// Respond to subs, gifts, raids, cheers, announcements and rituals
+    ///
+    /// If `opts.report_command_error` is set, errors are sent to the event's channel
+    pub fn on_event<F, Fut, O>(mut self, handler: F, opts: BindOptions) -> Self
+    where
+        F: Fn(Arc<tokio::sync::Mutex<T>>, EventContext) -> Fut + Send + Sync + 'static + Copy,
+        Fut: Future<Output = O> + Send + Sync + 'static,
+        O: Outcome,
+    {
+        let this = Arc::clone(&self.this);
+        let opts = opts;
+
+        let this = move |event: Arc<ChannelEvent>, writer: Writer| -> BoxFuture<'static> {
+            let this = Arc::clone(&this);
+
+            Box::pin(async move {
+                let context = EventContext {
+                    event: Arc::clone(&event),
+                    writer: writer.clone(),
+                };
+
+                if let Some(err) = handler(this, context).await.as_error() {
+                    if opts.report_command_error {
+                        EventContext { event, writer }.say(err);
+                    }
+                }
+            })
+        };
+
+        self.events.push(Arc::new(this) as _);
+        self
+    }
+
+    pub(crate) fn take_events(&mut self) -> Vec<Arc<EventCallable>> {
+        std::mem::take(&mut self.events)
+    }
+
     pub fn finish(self) -> Arc<Callable> {
         let this = Arc::new(self);
 
         Arc::new(
-            move |msg: Arc<Privmsg<'static>>, writer: Writer| -> BoxFuture<'static> {
+            move |msg: Arc<Privmsg<'static>>,
+                  writer: Writer,
+                  prefixes: Prefixes|
+                  -> BoxFuture<'static> {
                 let this = Arc::clone(&this);
                 let fut = async move {
                     let mut set = tokio::task::JoinSet::default();
                     for handler in this.handlers.iter().map(Arc::clone) {
                         let msg = Arc::clone(&msg);
                         let writer = writer.clone();
-                        set.spawn((handler)(msg, writer));
+                        set.spawn((handler)(msg, writer, prefixes.clone()));
                     }
 
                     while let Some(..) = set.join_next().await {}
@@ -160,14 +231,19 @@ where
         cmd: &Command,
         msg: &Privmsg<'_>,
         writer: &Writer,
+        prefixes: &Prefixes,
         opts: BindOptions,
     ) -> Option<Arguments> {
+        if !crate::CommandToggles::is_enabled(&msg.channel, &cmd.command) {
+            return None;
+        }
+
         let allowed = cmd.is_allowed(msg);
 
-        match Self::extract_args(cmd, msg) {
+        match Self::extract_args(cmd, msg, prefixes) {
             Ok(Some(map)) if allowed => return Some(map),
             Err(err) if allowed && opts.report_invalid_usage => {
-                writer.reply(msg, err);
+                let _ = writer.reply(msg, err);
                 return None;
             }
             Ok(None) => return None,
@@ -175,7 +251,7 @@ where
         }
 
         if opts.report_access_error && !allowed {
-            writer.reply(msg, "you cannot use that command");
+            let _ = writer.reply(msg, "you cannot use that command");
         }
 
         None
@@ -184,19 +260,34 @@ where
     pub(crate) fn extract_args(
         cmd: &Command,
         msg: &Privmsg<'_>,
+        prefixes: &Prefixes,
     ) -> Result<Option<Arguments>, String> {
-        if cmd.arguments.args.is_empty() && cmd.is_command_match(&msg.data) {
+        if cmd.arguments.args.is_empty()
+            && cmd.arguments.options.is_empty()
+            && cmd.is_prefixed_match(prefixes, &msg.data)
+        {
             return Ok(Some(Arguments::default()));
         }
 
-        let Some(tail) = cmd.tail(&msg.data) else {
+        let Some(tail) = cmd.tail_prefixed(prefixes, &msg.data) else {
             return Ok(None)
         };
 
         match cmd.arguments.extract(tail) {
-            Match::Required => Err(format!("usage: {} {}", cmd.command, cmd.arguments.usage)),
+            Match::Required => Err(format!(
+                "usage: {}{} {}",
+                prefixes.display(),
+                cmd.command,
+                cmd.arguments.usage
+            )),
             Match::NoMatch => Ok(None),
             Match::Match(map) => Ok(Some(Arguments { map })),
+            Match::Invalid(err) => Err(format!(
+                "{err}. usage: {}{} {}",
+                prefixes.display(),
+                cmd.command,
+                cmd.arguments.usage
+            )),
         }
     }
 }
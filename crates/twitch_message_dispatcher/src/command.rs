@@ -3,7 +3,7 @@ use std::collections::HashSet;
 
 use twitch_message::messages::Privmsg;
 
-use crate::ExampleArgs;
+use crate::{prefix::Prefixes, Cooldown, ExampleArgs};
 
 #[derive(Debug, Clone, PartialEq, PartialOrd, Deserialize)]
 pub struct Command {
@@ -19,6 +19,8 @@ pub struct Command {
     pub arguments: ExampleArgs,
     #[serde(default)]
     pub aliases: Vec<String>,
+    #[serde(default)]
+    pub cooldown: Cooldown,
 }
 
 impl serde::Serialize for Command {
@@ -27,12 +29,17 @@ impl serde::Serialize for Command {
         S: serde::Serializer,
     {
         use serde::ser::SerializeStruct as _;
-        let mut s = serializer.serialize_struct("Command", 5)?;
+        let mut s = serializer.serialize_struct("Command", 6)?;
         s.serialize_field("command", &self.command)?;
         s.serialize_field("arguments", &self.arguments.usage)?;
         s.serialize_field("aliases", &self.aliases)?;
         s.serialize_field("description", &self.description)?;
         s.serialize_field("allowed", &self.allowed)?;
+        if self.cooldown == Cooldown::default() {
+            s.skip_field("cooldown")?;
+        } else {
+            s.serialize_field("cooldown", &self.cooldown)?;
+        }
         s.end()
     }
 }
@@ -54,6 +61,7 @@ impl Command {
             aliases: Vec::new(),
             seen: HashSet::new(),
             allowed: Vec::new(),
+            cooldown: Cooldown::default(),
         }
     }
 
@@ -79,6 +87,21 @@ impl Command {
         self.possible_commands().any(|c| c == query)
     }
 
+    pub(crate) fn tail_prefixed<'a>(&self, prefixes: &Prefixes, data: &'a str) -> Option<&'a str> {
+        prefixes.strip(data).find_map(|data| self.tail(data))
+    }
+
+    pub(crate) fn is_prefixed_match(&self, prefixes: &Prefixes, query: &str) -> bool {
+        prefixes.strip(query).any(|query| self.is_command_match(query))
+    }
+
+    /// The rest of `data` if its first word is exactly this command (or an alias)
+    pub(crate) fn word_tail<'a>(&self, prefixes: &Prefixes, data: &'a str) -> Option<&'a str> {
+        let data = data.trim();
+        let (word, tail) = data.split_once(char::is_whitespace).unwrap_or((data, ""));
+        self.is_prefixed_match(prefixes, word).then(|| tail.trim())
+    }
+
     fn possible_commands(&self) -> impl Iterator<Item = &String> {
         std::iter::once(&self.command).chain(self.aliases.iter())
     }
@@ -92,6 +115,7 @@ pub struct CommandBuilder {
     aliases: Vec<String>,
     seen: HashSet<String>,
     allowed: Vec<Access>,
+    cooldown: Cooldown,
 }
 
 impl CommandBuilder {
@@ -111,6 +135,10 @@ impl CommandBuilder {
         self
     }
 
+    pub fn cooldown(self, cooldown: Cooldown) -> Self {
+        Self { cooldown, ..self }
+    }
+
     pub fn build(self) -> Result<Command, CommandBuilderError> {
         Ok(Command {
             id: (!self.id.is_empty())
@@ -129,6 +157,7 @@ impl CommandBuilder {
 
             aliases: self.aliases,
             arguments: self.args,
+            cooldown: self.cooldown,
         })
     }
 }
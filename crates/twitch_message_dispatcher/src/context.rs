@@ -1,7 +1,7 @@
 use std::{borrow::Borrow, ops::Deref, sync::Arc};
 
 use twitch_message::messages::Privmsg;
-use twitch_message_bot::Writer;
+use twitch_message_bot::{ChannelEvent, ReplyError, Writer};
 
 use crate::Arguments;
 
@@ -20,15 +20,15 @@ impl Context {
         &self.msg.channel
     }
 
-    pub fn user_id(&self) -> &str {
-        self.msg.user_id().expect("user-id attached").borrow()
+    pub fn user_id(&self) -> Option<&str> {
+        self.msg.user_id().map(|id| id.as_str())
     }
 
-    pub fn message_id(&self) -> &str {
-        self.msg.msg_id().expect("msg-id attached").borrow()
+    pub fn message_id(&self) -> Option<&str> {
+        self.msg.msg_id().map(|id| Borrow::<str>::borrow(id))
     }
 
-    pub fn reply(&self, data: impl ToString) {
+    pub fn reply(&self, data: impl ToString) -> Result<(), ReplyError> {
         self.writer.reply(&self.msg, data)
     }
 
@@ -44,3 +44,22 @@ impl std::ops::Index<&str> for Context {
         &self.arguments[index]
     }
 }
+
+pub struct EventContext {
+    pub event: Arc<ChannelEvent>,
+    pub writer: Writer,
+}
+
+impl EventContext {
+    pub fn channel(&self) -> &str {
+        &self.event.channel
+    }
+
+    pub fn user(&self) -> &str {
+        &self.event.user
+    }
+
+    pub fn say(&self, data: impl ToString) {
+        self.writer.say(&self.event.channel, data)
+    }
+}
@@ -0,0 +1,274 @@
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+use serde::{Deserialize, Serialize};
+use twitch_message::messages::Privmsg;
+
+/// How often a [`Command`](crate::Command) can be used
+#[derive(Debug, Clone, PartialEq, PartialOrd, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Cooldown {
+    /// Between any two uses
+    #[serde(with = "crate::duration::option", skip_serializing_if = "Option::is_none")]
+    pub global: Option<Duration>,
+    /// Between two uses by the same user, in the same channel
+    #[serde(with = "crate::duration::option", skip_serializing_if = "Option::is_none")]
+    pub user: Option<Duration>,
+    /// Between two uses in the same channel
+    #[serde(with = "crate::duration::option", skip_serializing_if = "Option::is_none")]
+    pub channel: Option<Duration>,
+    /// Moderators and the broadcaster aren't limited
+    pub exempt_moderators: bool,
+    /// Tell the user how long they have to wait, rather than ignoring them
+    pub reply: bool,
+}
+
+impl Default for Cooldown {
+    fn default() -> Self {
+        Self {
+            global: None,
+            user: None,
+            channel: None,
+            exempt_moderators: true,
+            reply: false,
+        }
+    }
+}
+
+impl Cooldown {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn global(self, global: Duration) -> Self {
+        Self {
+            global: Some(global),
+            ..self
+        }
+    }
+
+    pub fn per_user(self, user: Duration) -> Self {
+        Self {
+            user: Some(user),
+            ..self
+        }
+    }
+
+    pub fn per_channel(self, channel: Duration) -> Self {
+        Self {
+            channel: Some(channel),
+            ..self
+        }
+    }
+
+    pub fn exempt_moderators(self, exempt_moderators: bool) -> Self {
+        Self {
+            exempt_moderators,
+            ..self
+        }
+    }
+
+    pub fn reply(self, reply: bool) -> Self {
+        Self { reply, ..self }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.global.is_none() && self.user.is_none() && self.channel.is_none()
+    }
+}
+
+/// When a command can next be used
+#[derive(Default)]
+pub(crate) struct Cooldowns {
+    global: Option<Instant>,
+    users: HashMap<(Box<str>, Box<str>), Instant>,
+    channels: HashMap<Box<str>, Instant>,
+}
+
+impl Cooldowns {
+    /// Records a use of the command, or returns how long until it can be used
+    pub(crate) fn try_use(
+        &mut self,
+        cooldown: &Cooldown,
+        msg: &Privmsg<'_>,
+    ) -> Result<(), Duration> {
+        self.try_use_at(cooldown, msg, Instant::now())
+    }
+
+    fn try_use_at(
+        &mut self,
+        cooldown: &Cooldown,
+        msg: &Privmsg<'_>,
+        now: Instant,
+    ) -> Result<(), Duration> {
+        if cooldown.is_empty()
+            || cooldown.exempt_moderators && (msg.is_from_moderator() || msg.is_from_broadcaster())
+        {
+            return Ok(());
+        }
+
+        let channel: Box<str> = msg.channel.to_ascii_lowercase().into();
+        let user: Box<str> = match msg.user_id() {
+            Some(id) => id.as_str().into(),
+            None => msg.sender.to_ascii_lowercase().into(),
+        };
+        let user = (channel.clone(), user);
+
+        let remaining = [
+            self.global,
+            self.channels.get(&channel).copied(),
+            self.users.get(&user).copied(),
+        ]
+        .into_iter()
+        .flatten()
+        .filter_map(|until| until.checked_duration_since(now))
+        .filter(|d| !d.is_zero())
+        .max();
+
+        if let Some(remaining) = remaining {
+            return Err(remaining);
+        }
+
+        if let Some(global) = cooldown.global {
+            self.global = Some(expires(now, global));
+        }
+
+        if let Some(per_channel) = cooldown.channel {
+            self.channels.retain(|_, until| *until > now);
+            self.channels.insert(channel, expires(now, per_channel));
+        }
+
+        if let Some(per_user) = cooldown.user {
+            self.users.retain(|_, until| *until > now);
+            self.users.insert(user, expires(now, per_user));
+        }
+
+        Ok(())
+    }
+}
+
+/// Cooldowns longer than this (e.g. `100000000000d`) are treated as never expiring
+const FOREVER: Duration = Duration::from_secs(100 * 365 * 24 * 60 * 60);
+
+fn expires(now: Instant, cooldown: Duration) -> Instant {
+    now + cooldown.min(FOREVER)
+}
+
+#[cfg(test)]
+mod tests {
+    use twitch_message::builders::TagsBuilder;
+
+    use super::*;
+
+    fn privmsg(channel: &str, user_id: &str, badges: Option<&str>) -> Privmsg<'static> {
+        let mut tags = TagsBuilder::default()
+            .add("id", "test_msg_id")
+            .add("user-id", user_id);
+        if let Some(badges) = badges {
+            tags = tags.add("badges", badges);
+        }
+
+        Privmsg::builder()
+            .channel(channel)
+            .sender("test_user")
+            .data("!test")
+            .tags(tags.finish())
+            .finish_privmsg()
+            .unwrap()
+    }
+
+    const SECOND: Duration = Duration::from_secs(1);
+
+    #[test]
+    fn cooldown_global() {
+        let cooldown = Cooldown::new().global(10 * SECOND);
+        let mut cooldowns = Cooldowns::default();
+        let now = Instant::now();
+
+        assert_eq!(cooldowns.try_use_at(&cooldown, &privmsg("#a", "1", None), now), Ok(()));
+        assert_eq!(
+            cooldowns.try_use_at(&cooldown, &privmsg("#b", "2", None), now + SECOND),
+            Err(9 * SECOND)
+        );
+        assert_eq!(
+            cooldowns.try_use_at(&cooldown, &privmsg("#b", "2", None), now + 10 * SECOND),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn cooldown_per_user() {
+        let cooldown = Cooldown::new().per_user(10 * SECOND);
+        let mut cooldowns = Cooldowns::default();
+        let now = Instant::now();
+
+        let mut try_use = |channel: &str, user: &str, after: Duration| {
+            cooldowns.try_use_at(&cooldown, &privmsg(channel, user, None), now + after)
+        };
+
+        assert_eq!(try_use("#a", "1", Duration::ZERO), Ok(()));
+        assert_eq!(try_use("#a", "1", 4 * SECOND), Err(6 * SECOND));
+        assert_eq!(try_use("#a", "2", 4 * SECOND), Ok(()));
+        assert_eq!(try_use("#b", "1", 4 * SECOND), Ok(()));
+        assert_eq!(try_use("#a", "1", 10 * SECOND), Ok(()));
+    }
+
+    #[test]
+    fn cooldown_per_channel() {
+        let cooldown = Cooldown::new().per_channel(10 * SECOND);
+        let mut cooldowns = Cooldowns::default();
+        let now = Instant::now();
+
+        let mut try_use = |channel: &str, user: &str, after: Duration| {
+            cooldowns.try_use_at(&cooldown, &privmsg(channel, user, None), now + after)
+        };
+
+        assert_eq!(try_use("#a", "1", Duration::ZERO), Ok(()));
+        assert_eq!(try_use("#A", "2", SECOND), Err(9 * SECOND));
+        assert_eq!(try_use("#b", "2", SECOND), Ok(()));
+        assert_eq!(try_use("#a", "2", 10 * SECOND), Ok(()));
+    }
+
+    #[test]
+    fn cooldown_exempt_moderators() {
+        let now = Instant::now();
+        let moderator = privmsg("#a", "1", Some("moderator/1"));
+
+        let cooldown = Cooldown::new().per_user(10 * SECOND);
+        let mut cooldowns = Cooldowns::default();
+        assert_eq!(cooldowns.try_use_at(&cooldown, &moderator, now), Ok(()));
+        assert_eq!(cooldowns.try_use_at(&cooldown, &moderator, now), Ok(()));
+
+        let cooldown = cooldown.exempt_moderators(false);
+        let mut cooldowns = Cooldowns::default();
+        assert_eq!(cooldowns.try_use_at(&cooldown, &moderator, now), Ok(()));
+        assert!(cooldowns.try_use_at(&cooldown, &moderator, now).is_err());
+    }
+
+    #[test]
+    fn cooldown_overflow() {
+        let cooldown = Cooldown::new()
+            .global(Duration::MAX)
+            .per_channel(crate::duration::parse("100000000000d").unwrap());
+        let mut cooldowns = Cooldowns::default();
+        let now = Instant::now();
+
+        let msg = privmsg("#a", "1", None);
+        assert_eq!(cooldowns.try_use_at(&cooldown, &msg, now), Ok(()));
+        assert_eq!(cooldowns.try_use_at(&cooldown, &msg, now + SECOND), Err(FOREVER - SECOND));
+    }
+
+    #[test]
+    fn cooldown_serde() {
+        let cooldown = Cooldown::new()
+            .per_user(Duration::from_millis(250))
+            .per_channel(Duration::from_secs(90));
+
+        let data = serde_yaml::to_string(&cooldown).unwrap();
+        assert!(data.contains("250ms"), "{data}");
+        assert!(data.contains("1m30s"), "{data}");
+        assert_eq!(serde_yaml::from_str::<Cooldown>(&data).unwrap(), cooldown);
+    }
+}
@@ -2,25 +2,70 @@ use std::{collections::HashMap, sync::Arc};
 
 use twitch_message::messages::Privmsg;
 
-use twitch_message_bot::Writer;
+use twitch_message_bot::{ChannelEvent, ReplyError, Writer};
 
-use crate::{bind::Callable, help::Help, Bind, Command, Match, PrivmsgAccess};
+use crate::{
+    bind::{Callable, EventCallable},
+    help::Help,
+    prefix::{PrefixMap, Prefixes},
+    Access, Bind, Command, CommandToggles, Match, PrivmsgAccess,
+};
+
+type SaveToggles = dyn Fn(&CommandToggles) -> Result<(), Box<dyn std::error::Error + Send + Sync>>
+    + Send
+    + Sync;
+
+struct ToggleCommand {
+    cmd: Command,
+    save: Box<SaveToggles>,
+}
 
 #[derive(Default)]
 pub struct DispatcherBuilder {
     callables: Vec<Arc<Callable>>,
+    events: Vec<Arc<EventCallable>>,
     help_cmd: Option<Command>,
+    toggle_cmd: Option<ToggleCommand>,
+    prefixes: PrefixMap,
 }
 
 impl DispatcherBuilder {
-    pub fn add_bind<T>(mut self, bind: Bind<T>) -> Self
+    pub fn add_bind<T>(mut self, mut bind: Bind<T>) -> Self
     where
         T: Send + Sync + 'static,
     {
+        self.events.extend(bind.take_events());
         self.callables.push(bind.finish() as _);
         self
     }
 
+    /// Commands must start with this prefix, e.g. `!`
+    ///
+    /// Without a prefix, commands are matched as they were declared
+    pub fn with_prefix(self, prefix: impl ToString) -> Self {
+        self.with_prefixes([prefix])
+    }
+
+    /// Commands must start with one of these prefixes. The first one is shown in `help`
+    pub fn with_prefixes<I>(mut self, prefixes: I) -> Self
+    where
+        I: IntoIterator,
+        I::Item: ToString,
+    {
+        self.prefixes.set_global(Prefixes::new(prefixes));
+        self
+    }
+
+    /// Use these prefixes in `channel`, instead of the global ones
+    pub fn with_channel_prefixes<I>(mut self, channel: &str, prefixes: I) -> Self
+    where
+        I: IntoIterator,
+        I::Item: ToString,
+    {
+        self.prefixes.set_channel(channel, Prefixes::new(prefixes));
+        self
+    }
+
     pub fn with_help(self, help_command: &str, help_description: &str) -> Self {
         let help_cmd = Command::builder(
             concat!(
@@ -44,10 +89,49 @@ impl DispatcherBuilder {
         }
     }
 
+    /// Adds a moderator command to turn commands off and on in a channel,
+    /// e.g. `!command disable hello`
+    ///
+    /// `save` is called with the [`CommandToggles`] whenever they change
+    pub fn with_toggle_command<F, E>(self, toggle_command: &str, save: F) -> Self
+    where
+        F: Fn(&CommandToggles) -> Result<(), E> + Send + Sync + 'static,
+        E: Into<Box<dyn std::error::Error + Send + Sync>>,
+    {
+        let cmd = Command::builder(
+            concat!(
+                "__",
+                env!("CARGO_PKG_NAME"),
+                "@",
+                env!("CARGO_PKG_VERSION"),
+                "_toggle_command"
+            ),
+            toggle_command,
+            "enable or disable a command in this channel",
+        )
+        .args("<action> <command>".parse().unwrap())
+        .allow(Access::Moderator)
+        .allow(Access::Broadcaster)
+        .build()
+        .unwrap();
+
+        crate::help::help_registry().register(&cmd);
+        Self {
+            toggle_cmd: Some(ToggleCommand {
+                cmd,
+                save: Box::new(move |toggles: &CommandToggles| save(toggles).map_err(Into::into)),
+            }),
+            ..self
+        }
+    }
+
     pub fn into_dispatcher(self) -> Dispatcher {
         Dispatcher {
             callables: Arc::from(self.callables.into_boxed_slice()),
+            events: Arc::from(self.events.into_boxed_slice()),
             help_cmd: self.help_cmd.map(Arc::new),
+            toggle_cmd: self.toggle_cmd.map(Arc::new),
+            prefixes: Arc::new(parking_lot::RwLock::new(self.prefixes)),
         }
     }
 }
@@ -55,7 +139,10 @@ impl DispatcherBuilder {
 #[derive(Clone)]
 pub struct Dispatcher {
     callables: Arc<[Arc<Callable>]>,
+    events: Arc<[Arc<EventCallable>]>,
     help_cmd: Option<Arc<Command>>,
+    toggle_cmd: Option<Arc<ToggleCommand>>,
+    prefixes: Arc<parking_lot::RwLock<PrefixMap>>,
 }
 
 impl Dispatcher {
@@ -64,18 +151,33 @@ impl Dispatcher {
     }
 
     pub async fn dispatch_async(&self, msg: Arc<Privmsg<'static>>, writer: Writer) {
+        let prefixes = self.prefixes.read().for_channel(&msg.channel);
+
         if let Some(help) = &self.help_cmd {
-            if let Some(tail) = help.tail(&msg.data) {
+            if let Some(tail) = help.tail_prefixed(&prefixes, &msg.data) {
                 if let Match::Match(args) = help.arguments.extract(tail) {
-                    Self::try_send_help(&args, &msg, &writer);
+                    let _ = Self::try_send_help(&args, &msg, &writer, &prefixes);
                     return;
                 }
             }
         }
 
+        // anything the toggle command can't handle is dispatched like any other message
+        if let Some(toggle) = &self.toggle_cmd {
+            let args = toggle
+                .cmd
+                .word_tail(&prefixes, &msg.data)
+                .filter(|_| toggle.cmd.is_allowed(&msg))
+                .map(|tail| toggle.cmd.arguments.extract(tail));
+            if let Some(Match::Match(args)) = args {
+                let _ = Self::try_toggle(toggle, &args, &msg, &writer, &prefixes);
+                return;
+            }
+        }
+
         let mut set = tokio::task::JoinSet::default();
         for callable in self.callables.iter().map(Arc::clone) {
-            set.spawn((callable)(Arc::clone(&msg), writer.clone()));
+            set.spawn((callable)(Arc::clone(&msg), writer.clone(), prefixes.clone()));
         }
 
         while let Some(..) = set.join_next().await {}
@@ -86,6 +188,36 @@ impl Dispatcher {
         tokio::spawn(async move { this.dispatch_async(msg, writer).await });
     }
 
+    pub async fn dispatch_event_async(&self, event: Arc<ChannelEvent>, writer: Writer) {
+        let mut set = tokio::task::JoinSet::default();
+        for callable in self.events.iter().map(Arc::clone) {
+            set.spawn((callable)(Arc::clone(&event), writer.clone()));
+        }
+
+        while let Some(..) = set.join_next().await {}
+    }
+
+    pub fn dispatch_event(&self, event: Arc<ChannelEvent>, writer: Writer) {
+        let this = self.clone();
+        tokio::spawn(async move { this.dispatch_event_async(event, writer).await });
+    }
+
+    /// Use these prefixes in `channel`, instead of the global ones
+    pub fn set_channel_prefixes<I>(&self, channel: &str, prefixes: I)
+    where
+        I: IntoIterator,
+        I::Item: ToString,
+    {
+        self.prefixes
+            .write()
+            .set_channel(channel, Prefixes::new(prefixes))
+    }
+
+    /// Go back to using the global prefixes in `channel`
+    pub fn remove_channel_prefixes(&self, channel: &str) {
+        self.prefixes.write().remove_channel(channel)
+    }
+
     pub fn help_register(cmd: &Command) {
         crate::help::help_registry().register(cmd);
     }
@@ -94,22 +226,67 @@ impl Dispatcher {
         crate::help::help_registry().remove(&cmd.id);
     }
 
+    fn try_toggle(
+        toggle: &ToggleCommand,
+        args: &HashMap<String, String>,
+        msg: &Privmsg,
+        writer: &Writer,
+        prefixes: &Prefixes,
+    ) -> Result<(), ReplyError> {
+        let prefix = prefixes.display();
+        let usage = || {
+            let cmd = &toggle.cmd;
+            format!("usage: {prefix}{} {}", cmd.command, cmd.arguments.usage)
+        };
+
+        let (Some(action), Some(name)) = (args.get("action"), args.get("command")) else {
+            return writer.reply(msg, usage());
+        };
+
+        let name = prefixes.strip(name).next().unwrap_or(name);
+        if toggle.cmd.is_command_match(name) {
+            return writer.reply(msg, format!("{prefix}{name} cannot be disabled"));
+        }
+
+        let result = match action.as_str() {
+            "disable" | "off" => CommandToggles::disable(&msg.channel, name, &toggle.save)
+                .map(|changed| if changed { "disabled" } else { "already disabled" }),
+            "enable" | "on" => CommandToggles::enable(&msg.channel, name, &toggle.save)
+                .map(|changed| if changed { "enabled" } else { "already enabled" }),
+            _ => return writer.reply(msg, usage()),
+        };
+
+        match result {
+            Ok(state) => writer.reply(msg, format!("{prefix}{name} is {state} in this channel")),
+            Err(err) => writer.reply(msg, err),
+        }
+    }
+
     // TODO use the `Access` type to show the user what they can use
-    fn try_send_help(args: &HashMap<String, String>, msg: &Privmsg, writer: &Writer) {
+    fn try_send_help(
+        args: &HashMap<String, String>,
+        msg: &Privmsg,
+        writer: &Writer,
+        prefixes: &Prefixes,
+    ) -> Result<(), ReplyError> {
         use std::borrow::Cow;
 
+        let prefix = prefixes.display();
         let help = crate::help::help_registry();
         match args.get("command") {
             Some(cmd) => {
-                let Some(help) = help.lookup(cmd) else {
+                let lookup = prefixes.strip(cmd).find_map(|cmd| help.lookup(cmd));
+                let Some(help) = lookup.or_else(|| help.lookup(cmd)) else {
                     return writer.reply(msg, format!("unknown command: {cmd}"))
                 };
 
-                if !msg.is_allowed(&help.access) {
+                if !msg.is_allowed(&help.access)
+                    || !CommandToggles::is_enabled(&msg.channel, &help.command)
+                {
                     return writer.reply(msg, format!("unknown command: {cmd}"));
                 }
 
-                let mut reply = help.command.to_string();
+                let mut reply = format!("{prefix}{}", help.command);
                 if !help.usage.is_empty() {
                     reply.push(' ');
                     reply.push_str(&help.usage);
@@ -120,13 +297,17 @@ impl Dispatcher {
 
                 match help.aliases.len() {
                     0 => {}
-                    1 => reply.push_str(&format!("\navailable alias: {}", &help.aliases[0])),
+                    1 => {
+                        let alias = &help.aliases[0];
+                        reply.push_str(&format!("\navailable alias: {prefix}{alias}"))
+                    }
                     _ => {
                         reply.push_str("\navailable aliases: ");
                         for (i, alias) in help.aliases.iter().enumerate() {
                             if i > 0 {
                                 reply.push_str(", ");
                             }
+                            reply.push_str(prefix);
                             reply.push_str(alias);
                         }
                     }
@@ -139,15 +320,18 @@ impl Dispatcher {
                 msg,
                 help.get_all()
                     .filter(|(_, Help { access, .. })| msg.is_allowed(access))
+                    .filter(|(c, _)| CommandToggles::is_enabled(&msg.channel, c))
                     .map(|(c, help)| match help.aliases.len() {
-                        0 => Cow::from(c),
-                        1 => Cow::from(format!("{c} (alias: {})", help.aliases[0])),
+                        0 if prefix.is_empty() => Cow::from(c),
+                        0 => Cow::from(format!("{prefix}{c}")),
+                        1 => Cow::from(format!("{prefix}{c} (alias: {prefix}{})", help.aliases[0])),
                         _ => Cow::from(format!(
-                            "{c} (aliases: {})",
+                            "{prefix}{c} (aliases: {})",
                             help.aliases.iter().fold(String::new(), |mut a, c| {
                                 if !a.is_empty() {
                                     a.push_str(", ");
                                 }
+                                a.push_str(prefix);
                                 a.push_str(c);
                                 a
                             })
@@ -195,3 +379,186 @@ where
     I::Item: AsRef<str>,
 {
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        test::{MockHandler as _, Response},
+        BindOptions, Context,
+    };
+
+    /// Says the message back, without its `!`
+    fn echo_bind(id: &str, command: &str) -> Bind<()> {
+        let cmd = Command::builder(id, command, "says something").build().unwrap();
+        Bind::create(()).bind(
+            cmd,
+            |_, ctx: Context| async move { ctx.say(ctx.msg.data.trim_start_matches('!')) },
+            BindOptions::default(),
+        )
+    }
+
+    /// Replies with `reply`
+    fn reply_bind(id: &str, command: &str, reply: &'static str) -> Bind<()> {
+        let cmd = Command::builder(id, command, "says something").build().unwrap();
+        Bind::create(()).bind(
+            cmd,
+            move |_, ctx: Context| async move { ctx.reply(reply) },
+            BindOptions::default(),
+        )
+    }
+
+    #[tokio::test]
+    async fn global_prefix() {
+        let mut mock = Dispatcher::builder()
+            .add_bind(reply_bind("prefix_test_global", "pglobal", "hello"))
+            .with_prefix("!")
+            .into_dispatcher()
+            .mock();
+
+        let reply = |data| Response::new("test_msg_id", "#test", data);
+
+        // without the prefix, nothing matches
+        mock.privmsg_builder("pglobal").send().await;
+        mock.privmsg_builder("?pglobal").send().await;
+        mock.privmsg_builder("!pglobal").send().await;
+        assert_eq!(mock.read().await, reply("hello"));
+
+        mock.privmsg_builder("!pglobal").with_target("#other").send().await;
+        assert_eq!(mock.read().await, Response::new("test_msg_id", "#other", "hello"));
+    }
+
+    #[tokio::test]
+    async fn several_prefixes() {
+        let mut mock = Dispatcher::builder()
+            .add_bind(reply_bind("prefix_test_several", "pseveral", "hello"))
+            .with_prefixes(["!", "~"])
+            .into_dispatcher()
+            .mock();
+
+        let reply = |data| Response::new("test_msg_id", "#test", data);
+
+        mock.privmsg_builder("?pseveral").send().await;
+        mock.privmsg_builder("!pseveral").send().await;
+        assert_eq!(mock.read().await, reply("hello"));
+        mock.privmsg_builder("~pseveral").send().await;
+        assert_eq!(mock.read().await, reply("hello"));
+    }
+
+    #[tokio::test]
+    async fn channel_prefixes() {
+        let dispatcher = Dispatcher::builder()
+            .add_bind(reply_bind("prefix_test_channel", "pchannel", "hello"))
+            .with_prefix("!")
+            .with_channel_prefixes("#Override", ["?"])
+            .into_dispatcher();
+        let mut mock = dispatcher.clone().mock();
+
+        // the channel's prefixes replace the global ones
+        mock.privmsg_builder("!pchannel").with_target("#override").send().await;
+        mock.privmsg_builder("?pchannel").with_target("#override").send().await;
+        assert_eq!(mock.read().await, Response::new("test_msg_id", "#override", "hello"));
+
+        // other channels still use the global ones
+        mock.privmsg_builder("?pchannel").send().await;
+        mock.privmsg_builder("!pchannel").send().await;
+        assert_eq!(mock.read().await, Response::new("test_msg_id", "#test", "hello"));
+
+        dispatcher.set_channel_prefixes("#test", ["$"]);
+        mock.privmsg_builder("!pchannel").send().await;
+        mock.privmsg_builder("$pchannel").send().await;
+        assert_eq!(mock.read().await, Response::new("test_msg_id", "#test", "hello"));
+
+        dispatcher.remove_channel_prefixes("#override");
+        mock.privmsg_builder("?pchannel").with_target("#override").send().await;
+        mock.privmsg_builder("!pchannel").with_target("#override").send().await;
+        assert_eq!(mock.read().await, Response::new("test_msg_id", "#override", "hello"));
+    }
+
+    #[tokio::test]
+    async fn help_prefix() {
+        let cmd = Command::builder("prefix_test_help", "phelp", "says hello")
+            .build()
+            .unwrap();
+        let bind = Bind::create(()).bind(
+            cmd,
+            |_, ctx: Context| async move { ctx.reply("hello") },
+            BindOptions::default(),
+        );
+
+        let mut mock = Dispatcher::builder()
+            .add_bind(bind)
+            .with_prefixes(["!", "~"])
+            .with_channel_prefixes("#override", ["?"])
+            .with_help("help", "shows help")
+            .into_dispatcher()
+            .mock();
+
+        let expected = |channel, data| Response::new("test_msg_id", channel, data);
+
+        // the first prefix is the one that's shown
+        mock.privmsg_builder("~help phelp").send().await;
+        assert_eq!(mock.read().await, expected("#test", "!phelp: says hello"));
+
+        // the command can be looked up with or without a prefix
+        mock.privmsg_builder("!help ~phelp").send().await;
+        assert_eq!(mock.read().await, expected("#test", "!phelp: says hello"));
+
+        mock.privmsg_builder("!help phelp")
+            .with_target("#override")
+            .send()
+            .await;
+        mock.privmsg_builder("?help phelp")
+            .with_target("#override")
+            .send()
+            .await;
+        assert_eq!(mock.read().await, expected("#override", "?phelp: says hello"));
+    }
+
+    #[tokio::test]
+    async fn toggle_command() {
+        let mut mock = Dispatcher::builder()
+            .add_bind(echo_bind("toggle_test_hello", "!thello"))
+            .add_bind(echo_bind("toggle_test_list", "!tcmds"))
+            .with_toggle_command("!tcmd", |_: &CommandToggles| Ok::<_, std::io::Error>(()))
+            .into_dispatcher()
+            .mock();
+
+        let reply = |data| Response::new("test_msg_id", "#test", data);
+
+        mock.privmsg_builder("!thello").send().await;
+        assert_eq!(mock.read().await, Response::new(None, "#test", "thello"));
+
+        mock.privmsg_builder("!tcmd disable !thello")
+            .add_tag("badges", "moderator/1")
+            .send()
+            .await;
+        assert_eq!(mock.read().await, reply("!thello is disabled in this channel"));
+
+        // only disabled in this channel
+        mock.privmsg_builder("!thello").send().await;
+        mock.privmsg_builder("!thello")
+            .with_target("#other")
+            .send()
+            .await;
+        assert_eq!(mock.read().await, Response::new(None, "#other", "thello"));
+
+        // not a moderator, so this is dispatched like any other message
+        mock.privmsg_builder("!tcmd enable !thello").send().await;
+        // the toggle command doesn't match a longer command
+        mock.privmsg_builder("!tcmds")
+            .add_tag("badges", "moderator/1")
+            .send()
+            .await;
+        assert_eq!(mock.read().await, Response::new(None, "#test", "tcmds"));
+
+        mock.privmsg_builder("!tcmd enable !thello")
+            .add_tag("badges", "moderator/1")
+            .send()
+            .await;
+        assert_eq!(mock.read().await, reply("!thello is enabled in this channel"));
+
+        mock.privmsg_builder("!thello").send().await;
+        assert_eq!(mock.read().await, Response::new(None, "#test", "thello"));
+    }
+}
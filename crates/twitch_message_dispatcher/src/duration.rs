@@ -0,0 +1,201 @@
+use std::time::Duration;
+
+/// Parses durations like `30`, `30s`, `5m`, `1h30m` or `250ms`. A bare number is in seconds
+///
+/// Returns `None` if the duration doesn't fit in a [`Duration`]
+pub(crate) fn parse(input: &str) -> Option<Duration> {
+    let input = input.trim();
+    if input.is_empty() {
+        return None;
+    }
+
+    if let Ok(secs) = input.parse() {
+        return Some(Duration::from_secs(secs));
+    }
+
+    let mut total = Duration::ZERO;
+    let mut rest = input;
+    while !rest.is_empty() {
+        let end = rest.find(|c: char| !c.is_ascii_digit())?;
+        let (num, tail) = rest.split_at(end);
+        let num: u64 = num.parse().ok()?;
+
+        let end = tail.find(|c: char| c.is_ascii_digit()).unwrap_or(tail.len());
+        let (unit, tail) = tail.split_at(end);
+        let duration = match unit {
+            "ms" => Duration::from_millis(num),
+            "s" => Duration::from_secs(num),
+            "m" => Duration::from_secs(num.checked_mul(60)?),
+            "h" => Duration::from_secs(num.checked_mul(60 * 60)?),
+            "d" => Duration::from_secs(num.checked_mul(60 * 60 * 24)?),
+            _ => return None,
+        };
+        total = total.checked_add(duration)?;
+        rest = tail;
+    }
+
+    Some(total)
+}
+
+/// Formats a duration as `1h 2m 3s`, rounding up to the next second
+pub(crate) fn format(duration: Duration) -> String {
+    let secs = duration.as_secs() + u64::from(duration.subsec_nanos() > 0);
+    let (h, m, s) = (secs / 3600, secs % 3600 / 60, secs % 60);
+
+    match (h, m, s) {
+        (0, 0, s) => format!("{s}s"),
+        (0, m, 0) => format!("{m}m"),
+        (0, m, s) => format!("{m}m {s}s"),
+        (h, 0, 0) => format!("{h}h"),
+        (h, m, 0) => format!("{h}h {m}m"),
+        (h, m, s) => format!("{h}h {m}m {s}s"),
+    }
+}
+
+/// Formats a duration so [`parse`] reads it back unchanged, e.g. `1h30m` or `1s250ms`
+///
+/// Anything below a millisecond is dropped, as `parse` can't represent it
+pub(crate) fn format_exact(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    let (h, m, s) = (secs / 3600, secs % 3600 / 60, secs % 60);
+    let ms = u64::from(duration.subsec_millis());
+
+    let out = [(h, "h"), (m, "m"), (s, "s"), (ms, "ms")]
+        .into_iter()
+        .filter(|(n, _)| *n > 0)
+        .fold(String::new(), |out, (n, unit)| out + &format!("{n}{unit}"));
+
+    match out.is_empty() {
+        true => String::from("0s"),
+        false => out,
+    }
+}
+
+/// (De)serializes an optional duration as a string, e.g. `"1m30s"`
+pub(crate) mod option {
+    use std::time::Duration;
+
+    use serde::{Deserializer, Serializer};
+
+    pub fn serialize<S>(duration: &Option<Duration>, ser: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        match duration {
+            Some(duration) => ser.serialize_str(&super::format_exact(*duration)),
+            None => ser.serialize_none(),
+        }
+    }
+
+    pub fn deserialize<'de, D>(deser: D) -> Result<Option<Duration>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deser.deserialize_option(OptionVisitor)
+    }
+
+    struct OptionVisitor;
+
+    impl<'de> serde::de::Visitor<'de> for OptionVisitor {
+        type Value = Option<Duration>;
+
+        fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+            f.write_str("a duration, like `30s` or `1m30s`, or a number of seconds")
+        }
+
+        fn visit_none<E: serde::de::Error>(self) -> Result<Self::Value, E> {
+            Ok(None)
+        }
+
+        fn visit_unit<E: serde::de::Error>(self) -> Result<Self::Value, E> {
+            Ok(None)
+        }
+
+        fn visit_some<D: Deserializer<'de>>(self, deser: D) -> Result<Self::Value, D::Error> {
+            deser.deserialize_any(self)
+        }
+
+        fn visit_u64<E: serde::de::Error>(self, secs: u64) -> Result<Self::Value, E> {
+            Ok(Some(Duration::from_secs(secs)))
+        }
+
+        fn visit_i64<E: serde::de::Error>(self, secs: i64) -> Result<Self::Value, E> {
+            u64::try_from(secs)
+                .map(|secs| Some(Duration::from_secs(secs)))
+                .map_err(|_| E::custom(format!("invalid duration: {secs}")))
+        }
+
+        fn visit_str<E: serde::de::Error>(self, input: &str) -> Result<Self::Value, E> {
+            super::parse(input)
+                .map(Some)
+                .ok_or_else(|| E::custom(format!("invalid duration: {input}")))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_durations() {
+        let secs = Duration::from_secs;
+        let cases = [
+            ("30", Some(secs(30))),
+            (" 30s ", Some(secs(30))),
+            ("5m", Some(secs(300))),
+            ("1h30m", Some(secs(5400))),
+            ("2d", Some(secs(2 * 86400))),
+            ("1m250ms", Some(Duration::from_millis(60_250))),
+            ("", None),
+            ("m", None),
+            ("5x", None),
+            ("1h 30m", None),
+            ("-5s", None),
+            // overflows
+            ("99999999999999999999h", None),
+            ("18446744073709551615m", None),
+            ("307445734561825860h", None),
+            ("18446744073709551615s1s", None),
+        ];
+
+        for (input, expected) in cases {
+            assert_eq!(parse(input), expected, "{input:?}");
+        }
+    }
+
+    #[test]
+    fn format_exact_durations() {
+        let cases = [
+            (Duration::ZERO, "0s"),
+            (Duration::from_millis(250), "250ms"),
+            (Duration::from_millis(1500), "1s500ms"),
+            (Duration::from_secs(5400), "1h30m"),
+            (Duration::from_secs(2 * 86400 + 1), "48h1s"),
+        ];
+
+        for (duration, expected) in cases {
+            assert_eq!(format_exact(duration), expected, "{duration:?}");
+            assert_eq!(parse(expected), Some(duration), "{expected:?}");
+        }
+    }
+
+    #[test]
+    fn format_durations() {
+        let secs = Duration::from_secs;
+        let cases = [
+            (secs(0), "0s"),
+            (secs(45), "45s"),
+            (secs(120), "2m"),
+            (secs(150), "2m 30s"),
+            (secs(3600), "1h"),
+            (secs(3660), "1h 1m"),
+            (secs(3723), "1h 2m 3s"),
+            (Duration::from_millis(1500), "2s"),
+        ];
+
+        for (duration, expected) in cases {
+            assert_eq!(format(duration), expected, "{duration:?}");
+        }
+    }
+}
@@ -1,9 +1,13 @@
-use std::collections::{HashMap, HashSet};
+use std::{
+    borrow::Cow,
+    collections::{HashMap, HashSet},
+};
 
 #[derive(Default, Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
 pub struct ExampleArgs {
     pub usage: Box<str>,
     pub args: Box<[ArgType]>,
+    pub options: Box<[ArgOption]>,
 }
 
 impl<'de> serde::Deserialize<'de> for ExampleArgs {
@@ -22,9 +26,23 @@ impl<'de> serde::Deserialize<'de> for ExampleArgs {
 }
 
 impl ExampleArgs {
-    pub fn extract(&self, mut input: &str) -> Match {
+    pub fn extract(&self, input: &str) -> Match {
         use ArgKind::*;
 
+        let mut map = HashMap::default();
+        let rest;
+        let mut input = if self.options.is_empty() {
+            input
+        } else {
+            match self.extract_options(input, &mut map) {
+                Ok(tail) => {
+                    rest = tail;
+                    &*rest
+                }
+                Err(err) => return Match::Invalid(err),
+            }
+        };
+
         if input.is_empty() {
             if self.contains(&Required) {
                 return Match::Required;
@@ -39,34 +57,153 @@ impl ExampleArgs {
             return Match::NoMatch;
         }
 
-        let mut map = HashMap::default();
+        input = input.trim();
 
-        for ArgType { key, kind } in &*self.args {
-            match (kind, input.find(' ')) {
-                (Required | Optional, None) | (Variadic, ..) => {
-                    if !input.is_empty() {
-                        map.insert(key.to_string(), input.into());
-                    }
-                    break;
+        for ArgType { key, kind, .. } in &*self.args {
+            if let Variadic = kind {
+                if !input.is_empty() {
+                    map.insert(key.to_string(), input.into());
                 }
+                break;
+            }
 
-                (.., Some(pos)) => {
-                    let (head, tail) = input.split_at(pos);
-                    map.insert(key.to_string(), head.into());
-                    input = tail.trim();
+            match split_token(input) {
+                Ok(Some((head, tail))) => {
+                    map.insert(key.to_string(), head.into_owned());
+                    input = tail;
+                }
+                Ok(None) => break,
+                Err(..) => {
+                    return Match::Invalid(ArgError::UnterminatedQuote {
+                        key: key.to_string(),
+                    })
                 }
             }
         }
 
+        for ArgType { key, kind, value } in &*self.args {
+            let Some(input) = map.get_mut(&**key) else { continue };
+            if *value == ArgValue::Any {
+                continue;
+            }
+
+            let check = |token: &str| {
+                value.check(token).ok_or_else(|| ArgError::InvalidValue {
+                    key: key.to_string(),
+                    expected: *value,
+                    value: token.to_string(),
+                })
+            };
+
+            let checked = match kind {
+                Variadic => tokens(input)
+                    .map(|token| match token {
+                        Ok(token) => check(&token),
+                        Err(..) => Err(ArgError::UnterminatedQuote {
+                            key: key.to_string(),
+                        }),
+                    })
+                    .collect::<Result<Vec<_>, _>>()
+                    .map(|s| s.join(" ")),
+                _ => check(input),
+            };
+
+            match checked {
+                Ok(checked) => *input = checked,
+                Err(err) => return Match::Invalid(err),
+            }
+        }
+
         Match::Match(map)
     }
 
+    /// Removes the flags and options from `input`, returning what's left for the positional
+    /// arguments
+    fn extract_options(
+        &self,
+        mut input: &str,
+        map: &mut HashMap<String, String>,
+    ) -> Result<String, ArgError> {
+        // the words that aren't options are kept as they were written, including the whitespace
+        // between them, so variadic arguments see the original text
+        let mut rest = String::new();
+
+        loop {
+            input = input.trim_start();
+            if input.is_empty() {
+                break;
+            }
+
+            let word = &input[..input.find(char::is_whitespace).unwrap_or(input.len())];
+            let (name, inline) = match word.split_once('=') {
+                Some((name, _)) => (name, true),
+                None => (word, false),
+            };
+
+            let Some(option) = self.options.iter().find(|opt| opt.is_match(name, inline)) else {
+                let tail = match split_token(input) {
+                    Ok(Some((_, tail))) => tail,
+                    _ => "",
+                };
+                // this includes the whitespace up to the next word
+                rest.push_str(&input[..input.len() - tail.len()]);
+                input = tail;
+                continue;
+            };
+
+            let key = option.key.to_string();
+            // how the option was written, for the errors
+            let written = || match name.starts_with("--") {
+                true => name.to_string(),
+                false => format!("{name}="),
+            };
+            let Some(value) = option.value else {
+                map.insert(key, String::from("true"));
+                input = &input[word.len()..];
+                continue;
+            };
+
+            let after = if inline {
+                &input[name.len() + 1..]
+            } else {
+                &input[word.len()..]
+            };
+
+            let (token, tail) = match split_token(after) {
+                Ok(Some(token)) => token,
+                Ok(None) => return Err(ArgError::MissingValue { option: written() }),
+                Err(..) => return Err(ArgError::UnterminatedOption { option: written() }),
+            };
+
+            let Some(token) = value.check(&token) else {
+                return Err(ArgError::InvalidOption {
+                    option: written(),
+                    expected: value,
+                    value: token.into_owned(),
+                });
+            };
+
+            map.insert(key, token);
+            input = tail;
+        }
+
+        for ArgOption { key, default, .. } in &*self.options {
+            if let Some(default) = default {
+                map.entry(key.to_string())
+                    .or_insert_with(|| default.to_string());
+            }
+        }
+
+        rest.truncate(rest.trim_end().len());
+        Ok(rest)
+    }
+
     fn contains(&self, arg: &ArgKind) -> bool {
         self.args.iter().any(|ArgType { kind, .. }| kind == arg)
     }
 
     fn validate(args: &[ArgType]) -> Result<(), ExampleError> {
-        let duplicates = args.iter().fold(vec![], |mut a, ArgType { kind, key }| {
+        let duplicates = args.iter().fold(vec![], |mut a, ArgType { kind, key, .. }| {
             if matches!(kind, ArgKind::Variadic) {
                 a.push(key.to_string());
             }
@@ -78,7 +215,7 @@ impl ExampleArgs {
         }
 
         let mut iter = args.iter().peekable();
-        while let Some(ArgType { key, kind }) = iter.next() {
+        while let Some(ArgType { key, kind, .. }) = iter.next() {
             if matches!(kind, ArgKind::Optional)
                 && matches!(iter.peek(), Some(ArgType{kind, ..}) if matches!(kind, ArgKind::Required))
             {
@@ -109,6 +246,7 @@ impl std::str::FromStr for ExampleArgs {
 
         let mut seen = HashSet::new();
         let mut args = vec![];
+        let mut options = vec![];
 
         let all_alpha = move |s: &[u8], ctor: ArgKind| {
             if s.iter()
@@ -123,38 +261,77 @@ impl std::str::FromStr for ExampleArgs {
         };
 
         for token in input.split_whitespace() {
-            let mut append = |arg: &[_]| {
-                let data = &token[1..=arg.len()];
-                if !seen.insert(data) {
-                    return Err(Self::Err::Duplicate {
-                        key: data.to_string(),
+            let (arg, kind) = match token.as_bytes() {
+                [b'<', arg @ .., b'.', b'.', b'>'] => (arg, ArgKind::Variadic),
+                [b'<', arg @ .., b'?', b'>'] => (arg, ArgKind::Optional),
+                [b'<', arg @ .., b'>'] => (arg, ArgKind::Required),
+                [b'[', b'-', b'-', arg @ .., b']'] => {
+                    // [--flag] [--key:type] [--key:type=default] [--key=default]
+                    let arg = &token[3..3 + arg.len()];
+                    let (spec, default) = match arg.split_once('=') {
+                        Some((spec, default)) => (spec, Some(default)),
+                        None => (arg, None),
+                    };
+                    let (key, value) = match spec.split_once(':') {
+                        Some((key, ty)) => (key, Some(ty.parse()?)),
+                        None => (spec, default.map(|_| ArgValue::Any)),
+                    };
+
+                    all_alpha(key.as_bytes(), ArgKind::Optional)?;
+                    if !seen.insert(key) {
+                        return Err(Self::Err::Duplicate {
+                            key: key.to_string(),
+                        });
+                    }
+
+                    let default = match (value, default) {
+                        (Some(value), Some(default)) => match value.check(default) {
+                            Some(default) => Some(default.into()),
+                            None => {
+                                return Err(Self::Err::InvalidDefault {
+                                    key: key.to_string(),
+                                    default: default.to_string(),
+                                })
+                            }
+                        },
+                        _ => None,
+                    };
+
+                    options.push(ArgOption {
+                        key: key.into(),
+                        value,
+                        default,
                     });
+                    continue;
                 }
-                Ok(data.into())
+                _ => continue,
             };
 
-            let arg = match token.as_bytes() {
-                [b'<', arg @ .., b'.', b'.', b'>'] => ArgType {
-                    key: append(arg)?,
-                    kind: all_alpha(arg, ArgKind::Variadic)?,
-                },
-                [b'<', arg @ .., b'?', b'>'] => ArgType {
-                    key: append(arg)?,
-                    kind: all_alpha(arg, ArgKind::Optional)?,
-                },
-                [b'<', arg @ .., b'>'] => ArgType {
-                    key: append(arg)?,
-                    kind: all_alpha(arg, ArgKind::Required)?,
-                },
-                _ => continue,
+            // <key:type>
+            let arg = &token[1..=arg.len()];
+            let (key, value) = match arg.split_once(':') {
+                Some((key, ty)) => (key, ty.parse()?),
+                None => (arg, ArgValue::Any),
             };
 
-            args.push(arg);
+            let kind = all_alpha(key.as_bytes(), kind)?;
+            if !seen.insert(key) {
+                return Err(Self::Err::Duplicate {
+                    key: key.to_string(),
+                });
+            }
+
+            args.push(ArgType {
+                key: key.into(),
+                kind,
+                value,
+            });
         }
 
         Self::validate(&args).map(|_| Self {
             usage: input.into(),
             args: args.into(),
+            options: options.into(),
         })
     }
 }
@@ -169,6 +346,8 @@ pub enum ExampleError {
     OptionalBeforeRequired { key: String },
     EmptyInput,
     InvalidCommand { input: String },
+    UnknownType { ty: String },
+    InvalidDefault { key: String, default: String },
 }
 
 impl std::fmt::Display for ExampleError {
@@ -200,6 +379,10 @@ impl std::fmt::Display for ExampleError {
             }
             Self::EmptyInput => f.write_str("argument input was empty"),
             Self::InvalidCommand { input } => write!(f, "cannot parse '{input}' as a command"),
+            Self::UnknownType { ty } => write!(f, "unknown argument type: '{ty}'"),
+            Self::InvalidDefault { key, default } => {
+                write!(f, "invalid default for '{key}': '{default}'")
+            }
         }
     }
 }
@@ -229,6 +412,14 @@ impl Arguments {
         self.take_many_by(key, " ")
     }
 
+    /// Splits the argument on whitespace, keeping `"quoted parts"` together and unescaping them
+    pub fn take_many_quoted(&mut self, key: &str) -> Vec<String> {
+        self.map
+            .remove(key)
+            .map(|s| split_tokens(&s).into_iter().map(Cow::into_owned).collect())
+            .unwrap_or_default()
+    }
+
     pub fn take_many_by(&mut self, key: &str, sep: &str) -> Vec<String> {
         self.map
             .remove(key)
@@ -236,6 +427,11 @@ impl Arguments {
             .unwrap_or_default()
     }
 
+    /// Whether the flag (e.g. `--private`) was given
+    pub fn flag(&self, key: &str) -> bool {
+        self.map.contains_key(key)
+    }
+
     pub fn get(&self, key: &str) -> Option<&str> {
         self.map.get(key).map(|s| &**s)
     }
@@ -244,6 +440,14 @@ impl Arguments {
         self.get_many_by(key, " ")
     }
 
+    /// Splits the argument on whitespace, keeping `"quoted parts"` together and unescaping them
+    pub fn get_many_quoted(&self, key: &str) -> Vec<Cow<'_, str>> {
+        self.map
+            .get(key)
+            .map(|s| split_tokens(s))
+            .unwrap_or_default()
+    }
+
     pub fn get_many_by(&self, key: &str, sep: &str) -> Vec<&str> {
         self.map
             .get(key)
@@ -258,6 +462,70 @@ impl Arguments {
     {
         self.get(key).map(<str>::parse)
     }
+
+    /// The argument as a duration, for arguments declared like `<after:duration>`
+    pub fn get_duration(&self, key: &str) -> Option<std::time::Duration> {
+        self.get(key).and_then(crate::duration::parse)
+    }
+}
+
+/// Splits the next argument off of `input`, returning it and the rest of the input
+///
+/// An argument starting with `"` runs until the closing quote, inside of which `\` escapes
+/// the quote or another `\`. If the quote is never closed, the rest of the input is returned
+/// as the error. Apostrophes aren't quotes, so `'tis` is just a word
+fn split_token(input: &str) -> Result<Option<(Cow<'_, str>, &str)>, &str> {
+    let input = input.trim_start();
+    let quote = match input.chars().next() {
+        Some(quote @ '"') => quote,
+        Some(..) => {
+            let end = input.find(char::is_whitespace).unwrap_or(input.len());
+            let (head, tail) = input.split_at(end);
+            return Ok(Some((Cow::Borrowed(head), tail.trim_start())));
+        }
+        None => return Ok(None),
+    };
+
+    let body = &input[1..];
+    let mut out = String::new();
+    let mut escaped = false;
+    for (i, c) in body.char_indices() {
+        match c {
+            _ if escaped => {
+                if c != quote && c != '\\' {
+                    out.push('\\');
+                }
+                out.push(c);
+                escaped = false;
+            }
+            '\\' => escaped = true,
+            _ if c == quote => return Ok(Some((Cow::Owned(out), body[i + 1..].trim_start()))),
+            _ => out.push(c),
+        }
+    }
+
+    Err(input)
+}
+
+fn tokens(mut input: &str) -> impl Iterator<Item = Result<Cow<'_, str>, &str>> {
+    std::iter::from_fn(move || match split_token(input) {
+        Ok(Some((head, tail))) => {
+            input = tail;
+            Some(Ok(head))
+        }
+        Ok(None) => None,
+        Err(rest) => {
+            input = "";
+            Some(Err(rest))
+        }
+    })
+}
+
+/// Like [`tokens`] but an unterminated quote is kept as-is
+fn split_tokens(input: &str) -> Vec<Cow<'_, str>> {
+    tokens(input)
+        .map(|token| token.unwrap_or_else(Cow::Borrowed))
+        .collect()
 }
 
 impl std::ops::Index<&str> for Arguments {
@@ -274,12 +542,84 @@ pub enum Match {
     Required,
     NoMatch,
     Match(HashMap<String, String>),
+    Invalid(ArgError),
 }
 
+/// Why the input didn't fit the [`ExampleArgs`]
+#[non_exhaustive]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ArgError {
+    InvalidValue {
+        key: String,
+        expected: ArgValue,
+        value: String,
+    },
+    UnterminatedQuote {
+        key: String,
+    },
+    /// `option` is how the option was written, e.g. `--count` or `count=`
+    InvalidOption {
+        option: String,
+        expected: ArgValue,
+        value: String,
+    },
+    UnterminatedOption {
+        option: String,
+    },
+    MissingValue {
+        option: String,
+    },
+}
+
+impl std::fmt::Display for ArgError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::InvalidValue {
+                key,
+                expected,
+                value,
+            } => write!(f, "<{key}> must be {expected}, not '{value}'"),
+            Self::UnterminatedQuote { key } => write!(f, "<{key}> is missing a closing quote"),
+            Self::InvalidOption {
+                option,
+                expected,
+                value,
+            } => write!(f, "{option} must be {expected}, not '{value}'"),
+            Self::UnterminatedOption { option } => {
+                write!(f, "{option} is missing a closing quote")
+            }
+            Self::MissingValue { option } => write!(f, "{option} needs a value"),
+        }
+    }
+}
+
+impl std::error::Error for ArgError {}
+
 #[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
 pub struct ArgType {
     key: Box<str>,
     kind: ArgKind,
+    value: ArgValue,
+}
+
+/// A named option that can appear anywhere in the input, declared like
+/// `[--private]`, `[--title:str]` or `[--duration:u32=60]`
+///
+/// Options are given as `--key value`, `--key=value` or `key=value`. Flags have no value
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct ArgOption {
+    key: Box<str>,
+    value: Option<ArgValue>,
+    default: Option<Box<str>>,
+}
+
+impl ArgOption {
+    fn is_match(&self, name: &str, inline: bool) -> bool {
+        match name.strip_prefix("--") {
+            Some(name) => *self.key == *name && !(inline && self.value.is_none()),
+            None => inline && self.value.is_some() && *self.key == *name,
+        }
+    }
 }
 
 #[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
@@ -289,6 +629,94 @@ pub enum ArgKind {
     Variadic,
 }
 
+/// The type of an argument, declared like `<count:u32>`
+#[non_exhaustive]
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ArgValue {
+    #[default]
+    Any,
+    U32,
+    U64,
+    I32,
+    I64,
+    F64,
+    Bool,
+    /// A user name, with an optional `@` that is removed
+    User,
+    /// A [duration](std::time::Duration) like `30s` or `1h30m`
+    Duration,
+    /// An `http` or `https` url
+    Url,
+}
+
+impl ArgValue {
+    fn check(&self, input: &str) -> Option<String> {
+        fn parses<T: std::str::FromStr>(input: &str) -> bool {
+            input.parse::<T>().is_ok()
+        }
+
+        let ok = match self {
+            Self::Any => true,
+            Self::U32 => parses::<u32>(input),
+            Self::U64 => parses::<u64>(input),
+            Self::I32 => parses::<i32>(input),
+            Self::I64 => parses::<i64>(input),
+            Self::F64 => parses::<f64>(input),
+            Self::Bool => matches!(
+                &*input.to_ascii_lowercase(),
+                "true" | "false" | "yes" | "no" | "on" | "off"
+            ),
+            Self::User => {
+                let name = input.strip_prefix('@').unwrap_or(input);
+                let valid = (1..=25).contains(&name.len())
+                    && name.bytes().all(|c| c.is_ascii_alphanumeric() || c == b'_');
+                return valid.then(|| name.to_string());
+            }
+            Self::Duration => crate::duration::parse(input).is_some(),
+            Self::Url => input.split_once("://").map_or(false, |(scheme, rest)| {
+                matches!(&*scheme.to_ascii_lowercase(), "http" | "https") && !rest.is_empty()
+            }),
+        };
+
+        ok.then(|| input.to_string())
+    }
+}
+
+impl std::str::FromStr for ArgValue {
+    type Err = ExampleError;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        Ok(match input {
+            "" | "str" | "string" => Self::Any,
+            "u32" => Self::U32,
+            "u64" => Self::U64,
+            "i32" => Self::I32,
+            "i64" => Self::I64,
+            "f64" => Self::F64,
+            "bool" => Self::Bool,
+            "@user" | "user" => Self::User,
+            "duration" => Self::Duration,
+            "url" => Self::Url,
+            ty => return Err(ExampleError::UnknownType { ty: ty.to_string() }),
+        })
+    }
+}
+
+impl std::fmt::Display for ArgValue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Any => "some text",
+            Self::U32 | Self::U64 => "a positive number",
+            Self::I32 | Self::I64 => "a whole number",
+            Self::F64 => "a number",
+            Self::Bool => "yes or no",
+            Self::User => "a user name",
+            Self::Duration => "a duration (like 30s or 1h30m)",
+            Self::Url => "a link",
+        })
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -303,11 +731,168 @@ mod tests {
             "!hello <world..>",
             "!hello <world> <test?>",
             "!hello <world> <test?> <rest..>",
+            "!hello <world> [--loud] [--times:u32=1] [--greeting=hi]",
         ] {
             let _: ExampleArgs = input.parse().unwrap();
         }
     }
 
+    #[test]
+    fn example_args_quoted() {
+        let args: ExampleArgs = "!addquote <quote> <author> <tags..>".parse().unwrap();
+
+        let Match::Match(map) = args.extract(r#""hello world" 'tis "a \"b\"" "c d" don't"#)
+        else {
+            panic!("input should match")
+        };
+        assert_eq!(map["quote"], "hello world");
+        assert_eq!(map["author"], "'tis");
+
+        let mut tags = Arguments { map };
+        assert_eq!(tags.get_many("tags"), [r#""a"#, r#"\"b\"""#, r#""c"#, r#"d""#, "don't"]);
+        assert_eq!(tags.get_many_quoted("tags"), [r#"a "b""#, "c d", "don't"]);
+        assert_eq!(tags.take_many_quoted("tags"), [r#"a "b""#, "c d", "don't"]);
+
+        let Match::Invalid(err) = args.extract(r#""hello world museun"#) else {
+            panic!("input should be invalid")
+        };
+        assert_eq!(
+            err,
+            ArgError::UnterminatedQuote {
+                key: String::from("quote"),
+            }
+        );
+
+        // an apostrophe isn't a quote
+        let Match::Match(map) = args.extract("'em 'tis nice isn't it") else {
+            panic!("input should match")
+        };
+        assert_eq!(map["quote"], "'em");
+        assert_eq!(map["tags"], "nice isn't it");
+    }
+
+    #[test]
+    fn example_args_options() {
+        let args: ExampleArgs = "!clip <name?> [--title:str] [--private] [--duration:u32=60]"
+            .parse()
+            .unwrap();
+
+        let Match::Match(map) = args.extract(r#"--title "a b" best --private"#) else {
+            panic!("input should match")
+        };
+        let arguments = Arguments { map };
+        assert_eq!(arguments.get("name"), Some("best"));
+        assert_eq!(arguments.get("title"), Some("a b"));
+        assert_eq!(arguments.get("duration"), Some("60"));
+        assert!(arguments.flag("private"));
+
+        let Match::Match(map) = args.extract("duration=30 clip --title=hello") else {
+            panic!("input should match")
+        };
+        let arguments = Arguments { map };
+        assert_eq!(arguments.get("name"), Some("clip"));
+        assert_eq!(arguments.get("title"), Some("hello"));
+        assert_eq!(arguments.get("duration"), Some("30"));
+        assert!(!arguments.flag("private"));
+
+        let Match::Invalid(err) = args.extract("--duration=soon") else {
+            panic!("input should be invalid")
+        };
+        assert_eq!(err.to_string(), "--duration must be a positive number, not 'soon'");
+
+        let Match::Invalid(err) = args.extract("duration=soon") else {
+            panic!("input should be invalid")
+        };
+        assert_eq!(err.to_string(), "duration= must be a positive number, not 'soon'");
+
+        let Match::Invalid(err) = args.extract("--title") else {
+            panic!("input should be invalid")
+        };
+        assert_eq!(
+            err,
+            ArgError::MissingValue {
+                option: String::from("--title")
+            }
+        );
+        assert!(matches!(args.extract("a b"), Match::Match(..)));
+
+        // the variadic keeps its whitespace
+        let args: ExampleArgs = "!say <text..> [--loud]".parse().unwrap();
+        let Match::Match(map) = args.extract("hello   there --loud  \n  world ") else {
+            panic!("input should match")
+        };
+        assert_eq!(map["text"], "hello   there world");
+        assert_eq!(map["loud"], "true");
+
+        let Match::Match(map) = args.extract("--loud hello   there\n world") else {
+            panic!("input should match")
+        };
+        assert_eq!(map["text"], "hello   there\n world");
+
+        assert_eq!(
+            "[--a:u32=x]".parse::<ExampleArgs>().unwrap_err(),
+            ExampleError::InvalidDefault {
+                key: String::from("a"),
+                default: String::from("x"),
+            }
+        );
+    }
+
+    #[test]
+    fn example_args_typed() {
+        let args: ExampleArgs = "!give <user:@user> <count:u32> <reason..>".parse().unwrap();
+
+        let Match::Match(map) = args.extract("@museun 10 for being nice") else {
+            panic!("input should match")
+        };
+        assert_eq!(map["user"], "museun");
+        assert_eq!(map["count"], "10");
+        assert_eq!(map["reason"], "for being nice");
+
+        let Match::Invalid(err) = args.extract("museun ten") else {
+            panic!("input should be invalid")
+        };
+        assert_eq!(
+            err,
+            ArgError::InvalidValue {
+                key: String::from("count"),
+                expected: ArgValue::U32,
+                value: String::from("ten"),
+            }
+        );
+
+        let args: ExampleArgs = "<after:duration?> <links:url..>".parse().unwrap();
+        let Match::Match(map) = args.extract("1h30m") else {
+            panic!("input should match")
+        };
+        let arguments = Arguments { map };
+        assert_eq!(
+            arguments.get_duration("after"),
+            Some(std::time::Duration::from_secs(5400))
+        );
+        assert!(matches!(args.extract("soon"), Match::Invalid(..)));
+        for overflow in ["99999999999999999999h", "307445734561825860h"] {
+            assert!(matches!(
+                args.extract(overflow),
+                Match::Invalid(ArgError::InvalidValue {
+                    expected: ArgValue::Duration,
+                    ..
+                })
+            ));
+        }
+        assert!(matches!(
+            args.extract("5m https://example.com ftp://example.com"),
+            Match::Invalid(ArgError::InvalidValue { .. })
+        ));
+
+        assert_eq!(
+            "<a:nope>".parse::<ExampleArgs>().unwrap_err(),
+            ExampleError::UnknownType {
+                ty: String::from("nope")
+            }
+        );
+    }
+
     #[test]
     fn example_args_bad_input() {
         let bad_inputs = [
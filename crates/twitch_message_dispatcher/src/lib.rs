@@ -5,7 +5,10 @@ mod bind;
 pub use bind::{Bind, BindOptions};
 
 mod example_args;
-pub use example_args::{ArgKind, ArgType, Arguments, ExampleArgs, ExampleError, Match};
+pub use example_args::{
+    ArgError, ArgKind, ArgOption, ArgType, ArgValue, Arguments, ExampleArgs, ExampleError,
+    Match,
+};
 
 mod outcome;
 pub use outcome::Outcome;
@@ -13,12 +16,21 @@ pub use outcome::Outcome;
 mod command;
 pub use command::{Access, Command, CommandBuilder, CommandBuilderError, PrivmsgAccess};
 
+mod cooldown;
+pub use cooldown::Cooldown;
+
+mod duration;
+
 mod command_file;
 pub use command_file::{CommandFile, CommandFileError};
 
+mod toggles;
+pub use toggles::{CommandToggles, CommandTogglesError};
+
 mod help;
+mod prefix;
 
 mod context;
-pub use context::Context;
+pub use context::{Context, EventContext};
 
 pub mod test;
@@ -0,0 +1,65 @@
+use std::{collections::HashMap, sync::Arc};
+
+/// The command prefixes that apply to a message
+///
+/// With no prefixes, commands are matched as they were declared
+#[derive(Clone, Debug, Default)]
+pub(crate) struct Prefixes(Arc<[Box<str>]>);
+
+impl Prefixes {
+    pub(crate) fn new<I>(prefixes: I) -> Self
+    where
+        I: IntoIterator,
+        I::Item: ToString,
+    {
+        let prefixes = prefixes
+            .into_iter()
+            .map(|s| s.to_string().trim().into())
+            .filter(|s: &Box<str>| !s.is_empty())
+            .collect::<Vec<_>>();
+        Self(prefixes.into())
+    }
+
+    /// Every way `data` could be read as a command
+    pub(crate) fn strip<'a>(&'a self, data: &'a str) -> impl Iterator<Item = &'a str> + 'a {
+        let unprefixed = self.0.is_empty().then_some(data);
+        let prefixed = self.0.iter().filter_map(move |p| data.strip_prefix(&**p));
+        unprefixed.into_iter().chain(prefixed)
+    }
+
+    /// The prefix shown to users
+    pub(crate) fn display(&self) -> &str {
+        self.0.first().map(|s| &**s).unwrap_or_default()
+    }
+}
+
+#[derive(Default)]
+pub(crate) struct PrefixMap {
+    global: Prefixes,
+    channels: HashMap<Box<str>, Prefixes>,
+}
+
+impl PrefixMap {
+    pub(crate) fn set_global(&mut self, prefixes: Prefixes) {
+        self.global = prefixes;
+    }
+
+    pub(crate) fn set_channel(&mut self, channel: &str, prefixes: Prefixes) {
+        self.channels.insert(channel_key(channel), prefixes);
+    }
+
+    pub(crate) fn remove_channel(&mut self, channel: &str) {
+        self.channels.remove(&channel_key(channel));
+    }
+
+    pub(crate) fn for_channel(&self, channel: &str) -> Prefixes {
+        self.channels
+            .get(&channel_key(channel))
+            .unwrap_or(&self.global)
+            .clone()
+    }
+}
+
+fn channel_key(channel: &str) -> Box<str> {
+    channel.trim_start_matches('#').to_ascii_lowercase().into()
+}
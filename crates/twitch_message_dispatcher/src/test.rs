@@ -2,10 +2,11 @@ use std::{future::Future, sync::Arc};
 
 use tokio::sync::mpsc::UnboundedReceiver;
 use twitch_message::{builders::TagsBuilder, messages::Privmsg};
-use twitch_message_bot::{WriteKind, Writer};
+use twitch_message_bot::{QueuedWrite, Writer};
 
 use crate::{
     bind::{BindOptions, BoxFuture, Callable},
+    prefix::Prefixes,
     Bind, Command, Context, Dispatcher, Outcome,
 };
 
@@ -41,7 +42,7 @@ impl MockHandler for Arc<Callable> {
         let inner = Box::new({
             move |msg, writer| -> BoxFuture<'static> {
                 let this = Arc::clone(&self);
-                Box::pin((this)(msg, writer))
+                Box::pin((this)(msg, writer, Prefixes::default()))
             }
         });
         let (writer, recv) = Writer::new();
@@ -71,7 +72,7 @@ impl MockHandler for Dispatcher {
 }
 
 pub struct MockBinding {
-    recv: UnboundedReceiver<WriteKind>,
+    recv: UnboundedReceiver<QueuedWrite>,
     writer: Writer,
     inner: Box<dyn Fn(Arc<Privmsg<'static>>, Writer) -> BoxFuture<'static> + Send + Sync + 'static>,
 }
@@ -103,6 +104,7 @@ impl MockBinding {
             .recv()
             .await
             .expect("expected to read a response")
+            .kind
             .to_string();
         let msg = twitch_message::parse(&data).unwrap().message;
 
@@ -0,0 +1,189 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+
+#[non_exhaustive]
+#[derive(Debug)]
+pub enum CommandTogglesError {
+    UnknownCommand {
+        command: String,
+    },
+    CannotDeserialize {
+        error: Box<dyn std::error::Error + Send + Sync>,
+    },
+    CannotSave {
+        error: Box<dyn std::error::Error + Send + Sync>,
+    },
+}
+
+impl std::fmt::Display for CommandTogglesError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::UnknownCommand { command } => write!(f, "unknown command: {command}"),
+            Self::CannotDeserialize { error } => write!(f, "Cannot deserialize toggles: {error}"),
+            Self::CannotSave { error } => write!(f, "Cannot save toggles: {error}"),
+        }
+    }
+}
+
+impl std::error::Error for CommandTogglesError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::CannotDeserialize { error } | Self::CannotSave { error } => Some(&**error),
+            _ => None,
+        }
+    }
+}
+
+/// The commands that have been disabled in each channel
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct CommandToggles {
+    disabled: BTreeMap<String, BTreeSet<String>>,
+}
+
+impl CommandToggles {
+    pub fn load_from_str<E>(
+        data: &str,
+        deser: impl FnOnce(&str) -> Result<Self, E>,
+    ) -> Result<(), CommandTogglesError>
+    where
+        E: Into<Box<dyn std::error::Error + Send + Sync>>,
+    {
+        let this = deser(data).map_err(|error| CommandTogglesError::CannotDeserialize {
+            error: error.into(),
+        })?;
+
+        let _guard = UPDATE.lock();
+        *TOGGLES.write() = this;
+        Ok(())
+    }
+
+    /// Disable `command` (or one of its aliases) in `channel`
+    ///
+    /// Returns false if it was already disabled
+    pub fn disable<E>(
+        channel: &str,
+        command: &str,
+        save: impl FnOnce(&Self) -> Result<(), E>,
+    ) -> Result<bool, CommandTogglesError>
+    where
+        E: Into<Box<dyn std::error::Error + Send + Sync>>,
+    {
+        let command = Self::resolve(command)?;
+        Self::update(save, |this| {
+            this.disabled
+                .entry(channel_key(channel))
+                .or_default()
+                .insert(command)
+        })
+    }
+
+    /// Enable `command` (or one of its aliases) in `channel`
+    ///
+    /// Returns false if it wasn't disabled
+    pub fn enable<E>(
+        channel: &str,
+        command: &str,
+        save: impl FnOnce(&Self) -> Result<(), E>,
+    ) -> Result<bool, CommandTogglesError>
+    where
+        E: Into<Box<dyn std::error::Error + Send + Sync>>,
+    {
+        let command = Self::resolve(command)?;
+        Self::update(save, |this| {
+            let channel = channel_key(channel);
+            let Some(set) = this.disabled.get_mut(&channel) else {
+                return false;
+            };
+            let removed = set.remove(&command);
+            if set.is_empty() {
+                this.disabled.remove(&channel);
+            }
+            removed
+        })
+    }
+
+    pub fn is_enabled(channel: &str, command: &str) -> bool {
+        TOGGLES
+            .read()
+            .disabled
+            .get(&channel_key(channel))
+            .map_or(true, |set| !set.contains(command))
+    }
+
+    /// The commands that are disabled in `channel`
+    pub fn disabled(channel: &str) -> Vec<String> {
+        TOGGLES
+            .read()
+            .disabled
+            .get(&channel_key(channel))
+            .map(|set| set.iter().cloned().collect())
+            .unwrap_or_default()
+    }
+
+    fn resolve(command: &str) -> Result<String, CommandTogglesError> {
+        crate::help::help_registry()
+            .lookup(command)
+            .map(|help| help.command.clone())
+            .ok_or_else(|| CommandTogglesError::UnknownCommand {
+                command: command.to_string(),
+            })
+    }
+
+    fn update<E>(
+        save: impl FnOnce(&Self) -> Result<(), E>,
+        update: impl FnOnce(&mut Self) -> bool,
+    ) -> Result<bool, CommandTogglesError>
+    where
+        E: Into<Box<dyn std::error::Error + Send + Sync>>,
+    {
+        // only one update at a time, but dispatching can still read the toggles while we save
+        let _guard = UPDATE.lock();
+
+        let mut this = TOGGLES.read().clone();
+        if !update(&mut this) {
+            return Ok(false);
+        }
+
+        save(&this).map_err(|error| CommandTogglesError::CannotSave {
+            error: error.into(),
+        })?;
+
+        *TOGGLES.write() = this;
+        Ok(true)
+    }
+}
+
+fn channel_key(channel: &str) -> String {
+    channel.trim_start_matches('#').to_ascii_lowercase()
+}
+
+static TOGGLES: once_cell::sync::Lazy<parking_lot::RwLock<CommandToggles>> =
+    once_cell::sync::Lazy::new(Default::default);
+
+static UPDATE: parking_lot::Mutex<()> = parking_lot::const_mutex(());
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn toggles_failed_save() {
+        let cmd = crate::Command::builder("toggles_failed_save", "!tsave", "a test command")
+            .build()
+            .unwrap();
+        crate::Dispatcher::help_register(&cmd);
+
+        let err = CommandToggles::disable("#test", "!tsave", |_| Err("disk full")).unwrap_err();
+        assert!(matches!(err, CommandTogglesError::CannotSave { .. }));
+        assert!(CommandToggles::is_enabled("#test", "!tsave"));
+
+        let disabled = CommandToggles::disable("#test", "!tsave", |toggles| {
+            assert!(toggles.disabled["test"].contains("!tsave"));
+            Ok::<_, std::io::Error>(())
+        });
+        assert!(disabled.unwrap());
+        assert!(!CommandToggles::is_enabled("#TEST", "!tsave"));
+        assert!(CommandToggles::is_enabled("#other", "!tsave"));
+    }
+}
@@ -243,6 +243,7 @@ impl UserDefined {
             allowed: Vec::from_iter(allowed),
             arguments: <_>::default(),
             aliases: Vec::new(),
+            cooldown: <_>::default(),
         }
     }
 }